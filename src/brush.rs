@@ -0,0 +1,329 @@
+//! Painting particles into the world with brushes of different shapes and sizes, and the
+//! rectangle and bucket fill drawing tools.
+
+use std::collections::VecDeque;
+
+use rand::Rng;
+
+use crate::history::History;
+use crate::particle::Strain;
+use crate::world::{World, FOUR_ADJ_PARTICLES};
+
+// chance out of 100 that a spray brush paints each cell it covers
+const SPRAY_CHANCE: u8 = 10;
+
+/// The pattern a [`Brush`] stamps.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BrushShape {
+    Circle,
+    Square,
+    /// A circle that only paints a random scattering of its cells.
+    Spray,
+}
+
+impl BrushShape {
+    pub const ALL: [BrushShape; 3] = [BrushShape::Circle, BrushShape::Square, BrushShape::Spray];
+
+    /// The shape after this one in [`BrushShape::ALL`], wrapping around.
+    pub fn next(self) -> BrushShape {
+        let i = BrushShape::ALL.iter().position(|&s| s == self).unwrap();
+        BrushShape::ALL[(i + 1) % BrushShape::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BrushShape::Circle => "circle",
+            BrushShape::Square => "square",
+            BrushShape::Spray => "spray",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Brush {
+    pub shape: BrushShape,
+    // in cells from the centre, 0 paints a single cell
+    pub radius: usize,
+}
+
+impl Default for Brush {
+    // the plus shape painting used to be limited to
+    fn default() -> Self {
+        Brush {
+            shape: BrushShape::Circle,
+            radius: 1,
+        }
+    }
+}
+
+impl Brush {
+    pub const MAX_RADIUS: usize = 32;
+
+    /// Grows or shrinks the brush by `steps` cells, keeping it within `0..=MAX_RADIUS`.
+    pub fn resize(&mut self, steps: isize) {
+        self.radius = (self.radius as isize + steps).clamp(0, Brush::MAX_RADIUS as isize) as usize;
+    }
+
+    // whether the cell `dx`, `dy` from the centre is under the brush
+    fn covers(&self, dx: isize, dy: isize) -> bool {
+        let r = self.radius as isize;
+
+        match self.shape {
+            BrushShape::Square => dx.abs() <= r && dy.abs() <= r,
+            BrushShape::Circle | BrushShape::Spray => dx * dx + dy * dy <= r * r,
+        }
+    }
+}
+
+/// What clicking and dragging on the grid does.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Tool {
+    Paint,
+    Line,
+    Rect { filled: bool },
+    Fill,
+}
+
+impl Tool {
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Paint => "paint",
+            Tool::Line => "line",
+            Tool::Rect { filled: false } => "rectangle",
+            Tool::Rect { filled: true } => "filled rectangle",
+            Tool::Fill => "fill",
+        }
+    }
+}
+
+/// The mouse and the tool it is using for one tick.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ToolInput {
+    /// The cell under the cursor, which may be off the grid.
+    pub cursor: (isize, isize),
+    /// Whether the button that uses the tool is held.
+    pub down: bool,
+    pub tool: Tool,
+    pub brush: Brush,
+    pub strain: Strain,
+}
+
+/// A tool being used from one tick to the next: the stroke being painted or the line or
+/// rectangle being dragged out.
+#[derive(Clone, Debug, Default)]
+pub struct Drawing {
+    tool: Option<Tool>,
+    // the cell painted last tick while the button is held, to join strokes up
+    last_painted: Option<(isize, isize)>,
+    // where a line or rectangle was started, while it is being dragged out
+    drag_from: Option<(isize, isize)>,
+    was_down: bool,
+}
+
+impl Drawing {
+    /// Where the line or rectangle being dragged out was started.
+    pub fn drag_from(&self) -> Option<(isize, isize)> {
+        self.drag_from
+    }
+
+    /// Whether the button was held last tick.
+    pub fn was_down(&self) -> bool {
+        self.was_down
+    }
+
+    /// Uses the tool for a tick. Painting and filling happen while the button is held, lines
+    /// and rectangles once it is released, and switching tools drops anything half dragged out.
+    /// Every stroke and tool use is recorded in `history` first, so it can be undone.
+    pub fn update(&mut self, world: &mut World, history: &mut History, input: &ToolInput) {
+        let ToolInput {
+            cursor: at,
+            down,
+            tool,
+            brush,
+            strain,
+        } = *input;
+
+        if self.tool != Some(tool) {
+            self.tool = Some(tool);
+            self.drag_from = None;
+        }
+
+        let starting = match tool {
+            Tool::Paint | Tool::Fill => down && !self.was_down,
+            Tool::Line | Tool::Rect { .. } => !down && self.drag_from.is_some(),
+        };
+        if starting {
+            history.record(world);
+        }
+
+        match tool {
+            Tool::Paint if down => {
+                // joined up with where it was last tick
+                world.paint(&brush, self.last_painted.unwrap_or(at), at, strain);
+                self.last_painted = Some(at);
+            }
+            Tool::Line | Tool::Rect { .. } if down => {
+                self.drag_from.get_or_insert(at);
+            }
+            Tool::Line | Tool::Rect { .. } => {
+                // committed once the button is released
+                match (tool, self.drag_from.take()) {
+                    (Tool::Line, Some(from)) => world.paint(&brush, from, at, strain),
+                    (Tool::Rect { filled }, Some(from)) => {
+                        world.draw_rect(from, at, filled, strain)
+                    }
+                    _ => {}
+                }
+            }
+            Tool::Fill if down && !self.was_down => {
+                world.flood_fill(at.0 as usize, at.1 as usize, strain);
+            }
+            _ => {}
+        }
+
+        if !down {
+            self.last_painted = None;
+        }
+        self.was_down = down;
+    }
+}
+
+impl World {
+    /// Paints `strain` with `brush` along the line from `from` to `to`, stamping the brush at
+    /// every cell in between so fast strokes don't leave gaps. Painting only fills empty cells,
+    /// unless `strain` is empty, which erases. Anything off the grid is ignored.
+    pub fn paint(
+        &mut self,
+        brush: &Brush,
+        from: (isize, isize),
+        to: (isize, isize),
+        strain: Strain,
+    ) {
+        for (x, y) in line(from, to) {
+            self.stamp(brush, x, y, strain);
+        }
+    }
+
+    fn stamp(&mut self, brush: &Brush, x: isize, y: isize, strain: Strain) {
+        let r = brush.radius as isize;
+
+        for dy in -r..=r {
+            for dx in -r..=r {
+                if !brush.covers(dx, dy) {
+                    continue;
+                }
+
+                if brush.shape == BrushShape::Spray && self.rng().gen_range(0, 100) >= SPRAY_CHANCE
+                {
+                    continue;
+                }
+
+                self.paint_cell(x + dx, y + dy, strain);
+            }
+        }
+    }
+
+    /// Draws a rectangle of `strain` with opposite corners `from` and `to`, either solid or as a
+    /// one cell wide outline. Like [`World::paint`], it only fills empty cells unless erasing.
+    pub fn draw_rect(
+        &mut self,
+        from: (isize, isize),
+        to: (isize, isize),
+        filled: bool,
+        strain: Strain,
+    ) {
+        let (x0, x1) = (from.0.min(to.0), from.0.max(to.0));
+        let (y0, y1) = (from.1.min(to.1), from.1.max(to.1));
+
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                if filled || x == x0 || x == x1 || y == y0 || y == y1 {
+                    self.paint_cell(x, y, strain);
+                }
+            }
+        }
+    }
+
+    /// The cells joined to (x, y) through their edges that hold the same strain as it does,
+    /// including (x, y) itself.
+    pub fn connected_region(&self, x: usize, y: usize) -> Vec<(usize, usize)> {
+        if !self.in_bounds(x, y) {
+            return Vec::new();
+        }
+
+        let strain = self.get(x, y).strain;
+        let mut seen = vec![false; self.width() * self.height()];
+        let mut region = Vec::new();
+        let mut queue = VecDeque::new();
+
+        seen[x + self.width() * y] = true;
+        queue.push_back((x, y));
+
+        while let Some((x, y)) = queue.pop_front() {
+            region.push((x, y));
+
+            for (vx, vy) in FOUR_ADJ_PARTICLES.iter() {
+                let (ox, oy) = ((x as isize + vx) as usize, (y as isize + vy) as usize);
+
+                if self.in_bounds(ox, oy)
+                    && !seen[ox + self.width() * oy]
+                    && self.get(ox, oy).strain == strain
+                {
+                    seen[ox + self.width() * oy] = true;
+                    queue.push_back((ox, oy));
+                }
+            }
+        }
+
+        region
+    }
+
+    /// Replaces the [connected region](World::connected_region) around (x, y) with fresh
+    /// particles of `strain`.
+    pub fn flood_fill(&mut self, x: usize, y: usize, strain: Strain) {
+        if !self.in_bounds(x, y) || self.get(x, y).strain == strain {
+            return;
+        }
+
+        for (x, y) in self.connected_region(x, y) {
+            let p = self.create_particle(strain);
+            self.set(x, y, p);
+        }
+    }
+
+    fn paint_cell(&mut self, x: isize, y: isize, strain: Strain) {
+        let (x, y) = (x as usize, y as usize);
+
+        if self.in_bounds(x, y) && (self.is_particle_empty(x, y) || strain == Strain::EMPTY) {
+            let p = self.create_particle(strain);
+            self.spawn_particle(x, y, p);
+        }
+    }
+}
+
+// Every cell on the line from `from` to `to`, inclusive, by Bresenham's algorithm.
+fn line(from: (isize, isize), to: (isize, isize)) -> Vec<(isize, isize)> {
+    let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+    let (sx, sy) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+    let (mut x, mut y) = from;
+    let mut error = dx + dy;
+    let mut cells = Vec::with_capacity((dx - dy) as usize + 1);
+
+    loop {
+        cells.push((x, y));
+
+        if (x, y) == to {
+            return cells;
+        }
+
+        let e2 = error * 2;
+        if e2 >= dy {
+            error += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            error += dx;
+            y += sy;
+        }
+    }
+}
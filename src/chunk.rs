@@ -0,0 +1,716 @@
+//! The per-particle update rules, run over the grid one chunk at a time.
+//!
+//! The grid is split into square chunks coloured like a checkerboard with four colours, so no
+//! two chunks of the same colour touch, even at the corners. A tick updates each colour in turn,
+//! and every chunk of the current colour is stepped on its own rayon worker. A particle only
+//! reads and writes cells within [`REACH`] of itself, and chunks of one colour are a whole chunk
+//! apart, so concurrent chunks never touch the same cell.
+//!
+//! Each chunk draws from its own rng, seeded from the tick and the chunk's position, so the
+//! result is the same however rayon schedules the work.
+//!
+//! Chunks where nothing happened fall asleep and are skipped until something nearby changes.
+//! A chunk stays awake while any cell within its reach changes, while a temperature in it is
+//! still moving, or while a roll of the dice held back something that could have happened,
+//! like a liquid that did not flow this tick. Everything next to an awake chunk is woken too, as
+//! is anywhere the world is edited from outside.
+
+use nalgebra::Vector2;
+use rand::*;
+use rand_pcg::Pcg64Mcg;
+use rayon::prelude::*;
+
+use crate::material::{Material, Materials, Movement, MAX_DISPERSION};
+use crate::particle::{fresh_particle, same_state, Particle, Strain};
+use crate::world::FOUR_ADJ_PARTICLES;
+
+/// Width and height of a chunk in cells.
+pub(crate) const CHUNK_SIZE: usize = 32;
+
+/// The fastest a particle can travel, in cells per tick.
+const MAX_SPEED: f32 = 8.0;
+
+/// How far from its own cell a particle's update may read or write: as far as it can travel in a
+/// tick, plus the cell it collides with. Must be at most half of [`CHUNK_SIZE`] so that chunks of
+/// the same colour can never reach the same cell.
+pub(crate) const REACH: usize = MAX_SPEED as usize + 1;
+
+const _: () = assert!(REACH * 2 <= CHUNK_SIZE);
+const _: () = assert!(MAX_DISPERSION as usize <= REACH);
+
+// the speed a particle starts falling at, in cells per tick
+const FALL_SPEED: f32 = 1.0;
+/// Added to a falling particle's speed every tick, in cells per tick.
+pub(crate) const GRAVITY: f32 = 0.25;
+// particles landing slower than this do not splash
+const SPLASH_SPEED: f32 = 2.0;
+// the share of its sideways speed a particle keeps each tick while resting on something
+const FRICTION: f32 = 0.5;
+// sideways speeds below this come to a stop
+const STOP_SPEED: f32 = 0.5;
+
+/// The area covered by one chunk and whether it will be simulated next tick.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ChunkState {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub awake: bool,
+}
+
+/// Which chunks of a grid are awake.
+#[derive(Clone, Debug)]
+pub(crate) struct Chunks {
+    width: usize,
+    height: usize,
+    columns: usize,
+    rows: usize,
+    awake: Vec<bool>,
+}
+
+impl Chunks {
+    /// Chunks covering a `width` x `height` grid, all awake.
+    pub(crate) fn new(width: usize, height: usize) -> Chunks {
+        let columns = width.div_ceil(CHUNK_SIZE);
+        let rows = height.div_ceil(CHUNK_SIZE);
+
+        Chunks {
+            width,
+            height,
+            columns,
+            rows,
+            awake: vec![true; columns * rows],
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.awake.len()
+    }
+
+    /// The index of the chunk holding cell (x, y).
+    pub(crate) fn index_of(&self, x: usize, y: usize) -> usize {
+        x / CHUNK_SIZE + self.columns * (y / CHUNK_SIZE)
+    }
+
+    pub(crate) fn is_awake(&self, i: usize) -> bool {
+        self.awake[i]
+    }
+
+    /// Wakes the chunk holding cell (x, y) and the chunks around it.
+    pub(crate) fn wake_around(&mut self, x: usize, y: usize) {
+        self.wake_neighbours(x / CHUNK_SIZE, y / CHUNK_SIZE);
+    }
+
+    pub(crate) fn wake_all(&mut self) {
+        self.awake.iter_mut().for_each(|a| *a = true);
+    }
+
+    /// Puts every chunk to sleep except those `active` this tick and their neighbours.
+    pub(crate) fn settle(&mut self, active: &[bool]) {
+        self.awake.iter_mut().for_each(|a| *a = false);
+
+        for (i, _) in active.iter().enumerate().filter(|(_, &a)| a) {
+            self.wake_neighbours(i % self.columns, i / self.columns);
+        }
+    }
+
+    fn wake_neighbours(&mut self, cx: usize, cy: usize) {
+        for ny in cy.saturating_sub(1)..(cy + 2).min(self.rows) {
+            for nx in cx.saturating_sub(1)..(cx + 2).min(self.columns) {
+                self.awake[nx + self.columns * ny] = true;
+            }
+        }
+    }
+
+    pub(crate) fn states(&self) -> impl Iterator<Item = ChunkState> + '_ {
+        self.awake.iter().enumerate().map(move |(i, &awake)| {
+            let (x, y) = (i % self.columns * CHUNK_SIZE, i / self.columns * CHUNK_SIZE);
+
+            ChunkState {
+                x,
+                y,
+                width: CHUNK_SIZE.min(self.width - x),
+                height: CHUNK_SIZE.min(self.height - y),
+                awake,
+            }
+        })
+    }
+}
+
+// the grid shared between the workers of one pass, see the module docs for why this is sound
+#[derive(Clone, Copy)]
+struct Cells {
+    ptr: *mut Particle,
+    // which cells hold a particle that has been pushed aside this tick, moved along with it
+    displaced: *mut bool,
+    width: usize,
+    height: usize,
+}
+
+unsafe impl Send for Cells {}
+unsafe impl Sync for Cells {}
+
+/// Runs one tick of particle updates over the awake chunks of `grid`, returning how many
+/// particles were updated. Chunks where anything happened are flagged in `active`.
+pub(crate) fn step_chunks(
+    grid: &mut [Particle],
+    materials: &Materials,
+    chunks: &Chunks,
+    active: &mut [bool],
+    update: bool,
+    gravity: f32,
+    seed: u64,
+) -> u64 {
+    let (width, height) = (chunks.width, chunks.height);
+    assert_eq!(grid.len(), width * height);
+    debug_assert_eq!(active.len(), chunks.len());
+
+    // Every particle in an awake chunk is due an update this tick, however long its chunk slept
+    // and however it got into the grid, so flags left behind by skipped ticks are reset first
+    for state in chunks.states().filter(|s| s.awake) {
+        for y in state.y..state.y + state.height {
+            let row = state.x + width * y;
+            grid[row..row + state.width]
+                .iter_mut()
+                .for_each(|p| p.update = update);
+        }
+    }
+
+    let mut displaced = vec![false; grid.len()];
+    let cells = Cells {
+        ptr: grid.as_mut_ptr(),
+        displaced: displaced.as_mut_ptr(),
+        width,
+        height,
+    };
+
+    let (chunks_x, chunks_y) = (chunks.columns, chunks.rows);
+
+    let mut particles_updated = 0;
+
+    // bottom row of chunks first, to match the bottom to top scan within each chunk
+    for (colour_x, colour_y) in [(0, 1), (1, 1), (0, 0), (1, 0)] {
+        let colour: Vec<(usize, usize)> = (0..chunks_y)
+            .rev()
+            .filter(|cy| cy % 2 == colour_y)
+            .flat_map(|cy| {
+                (0..chunks_x)
+                    .filter(move |cx| cx % 2 == colour_x)
+                    .map(move |cx| (cx, cy))
+            })
+            .filter(|&(cx, cy)| chunks.is_awake(cx + cy * chunks_x))
+            .collect();
+
+        let results: Vec<(usize, u64, bool)> = colour
+            .into_par_iter()
+            .map(|(cx, cy)| {
+                let index = cx + cy * chunks_x;
+                let chunk_seed = seed ^ (index as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+
+                let mut chunk = Chunk {
+                    cells,
+                    materials,
+                    rng: Pcg64Mcg::seed_from_u64(chunk_seed),
+                    update,
+                    down: if gravity < 0.0 { -1 } else { 1 },
+                    gravity: gravity.abs(),
+                    particles_updated: 0,
+                    active: false,
+                    x0: cx * CHUNK_SIZE,
+                    y0: cy * CHUNK_SIZE,
+                    x1: ((cx + 1) * CHUNK_SIZE).min(width),
+                    y1: ((cy + 1) * CHUNK_SIZE).min(height),
+                };
+                chunk.step();
+                (index, chunk.particles_updated, chunk.active)
+            })
+            .collect();
+
+        for (index, updated, changed) in results {
+            particles_updated += updated;
+            active[index] |= changed;
+        }
+    }
+
+    particles_updated
+}
+
+struct Chunk<'a> {
+    cells: Cells,
+    materials: &'a Materials,
+    rng: Pcg64Mcg,
+    update: bool,
+    // which way is down, 1 unless gravity is upside down
+    down: isize,
+    gravity: f32,
+    particles_updated: u64,
+    // whether anything changed, or could have changed had the rng allowed it
+    active: bool,
+    // the cells this chunk updates, x0..x1 and y0..y1
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+}
+
+impl Chunk<'_> {
+    fn in_bounds(&self, x: usize, y: usize) -> bool {
+        x < self.cells.width && y < self.cells.height
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        assert!(self.in_bounds(x, y));
+        debug_assert!(
+            x + REACH >= self.x0
+                && x < self.x1 + REACH
+                && y + REACH >= self.y0
+                && y < self.y1 + REACH,
+            "({}, {}) is out of reach of the chunk",
+            x,
+            y
+        );
+
+        x + self.cells.width * y
+    }
+
+    fn get(&self, x: usize, y: usize) -> Particle {
+        // SAFETY: the index is inside the grid, and within reach of this chunk so no other
+        // worker is touching it
+        unsafe { *self.cells.ptr.add(self.index(x, y)) }
+    }
+
+    fn set(&mut self, x: usize, y: usize, p: Particle) {
+        self.active |= !same_state(&self.get(x, y), &p);
+
+        // SAFETY: as in `get`
+        unsafe { *self.cells.ptr.add(self.index(x, y)) = p }
+    }
+
+    // Moves the particle at (x1, y1) into (x2, y2), and whatever was there back into (x1, y1),
+    // marking it as displaced if it wasn't empty.
+    fn swap(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
+        self.active |= !same_state(&self.get(x1, y1), &self.get(x2, y2));
+
+        let pushed = !self.is_particle_empty(x2, y2);
+        let (i1, i2) = (self.index(x1, y1), self.index(x2, y2));
+
+        // SAFETY: as in `get`, and `ptr::swap` allows the two cells to be the same
+        unsafe {
+            std::ptr::swap(self.cells.ptr.add(i1), self.cells.ptr.add(i2));
+            std::ptr::swap(self.cells.displaced.add(i1), self.cells.displaced.add(i2));
+
+            if pushed && i1 != i2 {
+                *self.cells.displaced.add(i1) = true;
+            }
+        }
+    }
+
+    fn is_displaced(&self, x: usize, y: usize) -> bool {
+        // SAFETY: as in `get`
+        unsafe { *self.cells.displaced.add(self.index(x, y)) }
+    }
+
+    fn is_particle_empty(&self, x: usize, y: usize) -> bool {
+        self.get(x, y).strain == Strain::EMPTY
+    }
+
+    // moves `val` cells down, or up if it is negative
+    fn apply_gravity(&mut self, x: usize, y: usize, val: isize) -> bool {
+        let dy = val * self.down;
+
+        if self.can_displace(x, y, 0, dy) {
+            self.swap(x, y, x, (y as isize + dy) as usize);
+
+            return true;
+        }
+
+        false
+    }
+
+    // Can a particle of `material` move into (x, y), `dy` rows below where it is now? Anything
+    // can move into empty space and nothing can displace an immovable solid. Otherwise particles
+    // sink through and push aside anything lighter, and float up through heavier liquids and
+    // gases. Whatever is displaced swaps places with the particle, and is not pushed aside again
+    // that tick, or a column of sinking particles would carry it all the way up in one go.
+    fn can_enter(&self, material: &Material, x: usize, y: usize, dy: isize) -> bool {
+        if !self.in_bounds(x, y) {
+            return false;
+        }
+
+        let other = self.get(x, y).strain;
+        if other == Strain::EMPTY {
+            return true;
+        }
+
+        let other = &self.materials[other];
+        if other.is_immovable() || self.is_displaced(x, y) {
+            return false;
+        }
+
+        if dy < 0 {
+            material.density < other.density
+                && matches!(other.movement, Movement::Liquid | Movement::Gas)
+        } else {
+            material.density > other.density
+        }
+    }
+
+    // can the particle at (x, y) move into (x + dx, y + dy)?
+    fn can_displace(&self, x: usize, y: usize, dx: isize, dy: isize) -> bool {
+        let (ox, oy) = ((x as isize + dx) as usize, (y as isize + dy) as usize);
+
+        self.can_enter(
+            &self.materials[self.get(x, y).strain],
+            ox,
+            oy,
+            dy * self.down,
+        )
+    }
+
+    // Accelerates the particle at (x, y) under gravity and moves it along its velocity, a cell at
+    // a time so it stops at whatever it hits. Landing fast throws it, and whatever it landed on,
+    // sideways in opposite directions, and resting on something slows its sideways movement.
+    fn apply_motion(&mut self, x: usize, y: usize) -> bool {
+        let down = self.down;
+        let mut v = self.get(x, y).velocity;
+
+        // how fast it is falling, whichever way down is
+        let mut speed = v.y * down as f32;
+        if self.can_displace(x, y, 0, down) {
+            speed = (speed + self.gravity).clamp(FALL_SPEED, MAX_SPEED);
+        } else {
+            speed = 0.0;
+        }
+        v.y = speed * down as f32;
+        v.x = v.x.clamp(-MAX_SPEED, MAX_SPEED);
+
+        let (dx, dy) = (v.x.round() as isize, v.y.round() as isize);
+        let steps = dx.abs().max(dy.abs());
+        let (mut cx, mut cy) = (x, y);
+
+        for i in 1..=steps {
+            let tx = x as isize + (dx as f32 * i as f32 / steps as f32).round() as isize;
+            let ty = y as isize + (dy as f32 * i as f32 / steps as f32).round() as isize;
+            let (sx, sy) = (tx - cx as isize, ty - cy as isize);
+
+            if !self.can_displace(cx, cy, sx, sy) {
+                break;
+            }
+
+            let (nx, ny) = (tx as usize, ty as usize);
+            self.swap(cx, cy, nx, ny);
+            (cx, cy) = (nx, ny);
+        }
+
+        if !self.can_displace(cx, cy, 0, down) {
+            let splash = self.materials[self.get(cx, cy).strain].splash;
+
+            if speed >= SPLASH_SPEED && splash > 0 {
+                let dir = if self.rng.gen() { -1.0 } else { 1.0 };
+                let thrown = speed * splash as f32 / 100.0;
+                v.x += dir * thrown;
+
+                // what it hit is knocked the other way, if it can move at all
+                let by = (cy as isize + down) as usize;
+                if self.in_bounds(cx, by) {
+                    let mut below = self.get(cx, by);
+                    if matches!(
+                        self.materials[below.strain].movement,
+                        Movement::Powder | Movement::Liquid
+                    ) {
+                        below.velocity.x -= dir * thrown / 2.0;
+                        self.set(cx, by, below);
+                    }
+                }
+            }
+
+            v.y = 0.0;
+            v.x *= FRICTION;
+        }
+
+        if v.x != 0.0 && !self.can_displace(cx, cy, v.x.signum() as isize, 0) {
+            v.x = 0.0;
+        }
+        if v.x.abs() < STOP_SPEED {
+            v.x = 0.0;
+        }
+
+        let mut p = self.get(cx, cy);
+        p.velocity = v;
+        self.set(cx, cy, p);
+
+        (cx, cy) != (x, y)
+    }
+
+    fn apply_tumble(&mut self, x: usize, y: usize) -> bool {
+        let mut translate: Vector2<isize> = Vector2::new(0, 0);
+
+        if self.can_displace(x, y, -1, self.down) {
+            // Move down left
+            translate.x = -1;
+            translate.y = self.down;
+        } else if self.can_displace(x, y, 1, self.down) {
+            // Move down right
+            translate.x = 1;
+            translate.y = self.down;
+        }
+
+        if translate.x != 0 || translate.y != 0 {
+            self.swap(
+                x,
+                y,
+                (x as isize + translate.x) as usize,
+                (y as isize + translate.y) as usize,
+            );
+
+            return true;
+        }
+
+        false
+    }
+
+    // Flows up to `distance` cells sideways in a random direction, or the other way if that is
+    // blocked, stopping early over any drop so pools level out instead of piling up.
+    fn apply_spread(&mut self, x: usize, y: usize, distance: u8) -> bool {
+        let dir: isize = if self.rng.gen() { -1 } else { 1 };
+
+        for dir in [dir, -dir] {
+            let n = self.spread_distance(x, y, dir, distance);
+
+            if n > 0 {
+                // across the empty cells to the last of them, then on into whatever it stopped
+                // at, so anything pushed aside only moves back by one cell
+                let last = (x as isize + dir * (n - 1)) as usize;
+                self.swap(x, y, last, y);
+                self.swap(last, y, (x as isize + dir * n) as usize, y);
+
+                return true;
+            }
+        }
+
+        false
+    }
+
+    // How many cells the particle at (x, y) can flow across towards `dir`. It flows over empty
+    // space, stopping at anything lighter it pushes aside.
+    fn spread_distance(&self, x: usize, y: usize, dir: isize, distance: u8) -> isize {
+        let material = &self.materials[self.get(x, y).strain];
+        let mut n = 0;
+
+        while n < distance as isize {
+            let to = (x as isize + dir * (n + 1)) as usize;
+            if !self.can_enter(material, to, y, 0) {
+                break;
+            }
+
+            n += 1;
+
+            let below = (y as isize + self.down) as usize;
+            if !self.is_particle_empty(to, y) || self.can_enter(material, to, below, 1) {
+                break;
+            }
+        }
+
+        n
+    }
+
+    fn can_spread(&self, x: usize, y: usize, dir: isize) -> bool {
+        self.can_displace(x, y, dir, 0)
+    }
+
+    fn can_tumble(&self, x: usize, y: usize) -> bool {
+        self.can_displace(x, y, -1, self.down) || self.can_displace(x, y, 1, self.down)
+    }
+
+    // falls or slides, then tumbles down diagonally
+    fn apply_powder(&mut self, x: usize, y: usize) -> bool {
+        self.apply_motion(x, y) || self.apply_tumble(x, y)
+    }
+
+    // falls or slides, then tumbles or spreads up to `dispersion` cells sideways if `flow` allows
+    // it this tick
+    fn apply_liquid(&mut self, x: usize, y: usize, flow: bool, dispersion: u8) -> bool {
+        self.apply_motion(x, y)
+            || (flow && (self.apply_tumble(x, y) || self.apply_spread(x, y, dispersion)))
+    }
+
+    // rises, then rises diagonally, then drifts sideways
+    fn apply_gas(&mut self, x: usize, y: usize) -> bool {
+        let dir: isize = if self.rng.gen() { -1 } else { 1 };
+
+        let up = -self.down;
+        let moves = [(0, up), (dir, up), (-dir, up), (dir, 0)];
+        let to = moves
+            .iter()
+            .find(|&&(dx, dy)| self.can_displace(x, y, dx, dy));
+
+        match to {
+            Some(&(dx, dy)) => {
+                self.swap(x, y, (x as isize + dx) as usize, (y as isize + dy) as usize);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // turns `p` into `strain` in place, keeping its temperature
+    fn transform(&mut self, p: &mut Particle, strain: Strain) {
+        p.strain = strain;
+        p.lifetime = self.materials[strain].base_lifetime(&mut self.rng);
+    }
+
+    // Melts, boils, freezes or ignites `p` if its temperature calls for it. A change of state
+    // soaks up or gives off heat, which is modelled by settling the particle at the transition
+    // temperature, so boiled water does not immediately reboil when its steam condenses.
+    fn change_phase(&mut self, material: &Material, p: &mut Particle) {
+        let t = p.temperature;
+
+        let state = material
+            .melting
+            .filter(|m| t >= m.at)
+            .or_else(|| material.boiling.filter(|b| t >= b.at))
+            .or_else(|| material.freezing.filter(|f| t <= f.at));
+
+        if let Some(s) = state {
+            self.transform(p, s.into);
+            p.temperature = s.at;
+            return;
+        }
+
+        match material.ignition {
+            Some(i) if t >= i.at => {
+                if self.rng.gen_range(0, 100) < material.ignite_chance {
+                    self.transform(p, i.into);
+                } else {
+                    self.active = true;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Update particles - bottom to top, whichever way down is; left to right
+    fn step(&mut self) {
+        for row in 0..self.y1 - self.y0 {
+            let y = if self.down > 0 {
+                self.y1 - 1 - row
+            } else {
+                self.y0 + row
+            };
+
+            for x in self.x0..self.x1 {
+                self.update_particle(x, y);
+            }
+        }
+    }
+
+    fn update_particle(&mut self, x: usize, y: usize) {
+        let materials = self.materials;
+        let mut p = self.get(x, y);
+
+        // check if dead
+        if p.lifetime == 0 {
+            let into = materials[p.strain].dies_into;
+            self.transform(&mut p, into);
+
+            // save
+            self.set(x, y, p);
+            return;
+        }
+
+        // check the particle has not been updated this frame & ensure it isn't empty
+        if p.update != self.update || p.strain == Strain::EMPTY {
+            return;
+        }
+
+        p.update = !p.update;
+
+        // decrease lifetime if needed
+        if p.lifetime > 0 {
+            p.lifetime -= 1;
+        }
+
+        // burning particles stay hot until they burn out
+        if let Some(t) = materials[p.strain].burns_at {
+            p.temperature = t;
+        }
+
+        // melt, boil, freeze or catch light
+        self.change_phase(&materials[p.strain], &mut p);
+
+        // Attempt reaction
+        for r in materials[p.strain].reactions.iter() {
+            for (vx, vy) in FOUR_ADJ_PARTICLES.iter() {
+                let (ox, oy) = ((x as isize + vx) as usize, (y as isize + vy) as usize);
+
+                if self.in_bounds(ox, oy) && self.get(ox, oy).strain == r.with {
+                    if self.rng.gen_range(0, 100) < r.chance {
+                        // Reaction successful, the product starts at its own temperature so
+                        // quenched embers stay out
+                        self.transform(&mut p, r.into);
+                        p.temperature = materials[r.into].base_temperature();
+                        break;
+                    }
+
+                    self.active = true;
+                }
+            }
+        }
+
+        // save state to grid
+        self.set(x, y, p);
+
+        let material = &materials[p.strain];
+
+        // Attempt to emit from above
+        for emit_above in material.emits_above.iter() {
+            let above = (y as isize - self.down) as usize;
+            if self.in_bounds(x, above) && self.is_particle_empty(x, above) {
+                if self.rng.gen_range(0, 100) < emit_above.chance {
+                    let particle = Particle {
+                        update: p.update,
+                        ..fresh_particle(self.materials, emit_above.strain, &mut self.rng)
+                    };
+                    self.set(x, above, particle);
+                } else {
+                    self.active = true;
+                }
+            }
+        }
+
+        // select particle update behaviour depending on its material
+        match material.movement {
+            Movement::Static => {}
+            Movement::Powder => {
+                self.apply_powder(x, y);
+            }
+            Movement::Liquid => {
+                // Randomly dont move to appear thicker
+                let flow = material.flow_chance >= 100
+                    || self.rng.gen_range(0, 100) < material.flow_chance;
+                if !self.apply_liquid(x, y, flow, material.dispersion) && !flow {
+                    self.active |= self.can_tumble(x, y)
+                        || self.can_spread(x, y, -1)
+                        || self.can_spread(x, y, 1);
+                }
+            }
+            Movement::Flame => {
+                // flickers at random, so never settles
+                self.active = true;
+
+                if self.rng.gen() {
+                    self.apply_gravity(x, y, -1);
+                }
+                if self.rng.gen() {
+                    self.apply_spread(x, y, 1);
+                }
+            }
+            Movement::Gas => {
+                self.apply_gas(x, y);
+            }
+        }
+
+        self.particles_updated += 1;
+    }
+}
@@ -0,0 +1,103 @@
+//! Commands typed into the in-game console.
+
+use std::error::Error;
+use std::str::FromStr;
+
+use falling_sand_rs::{Strain, World};
+
+const HELP: &str = "fill <material> <x0> <y0> <x1> <y1>, clear, seed <n>, save <file>, \
+                    load <file>, set gravity <n>";
+
+/// Runs one line typed into the console against `world`, returning anything to print.
+pub fn run(world: &mut World, line: &str) -> Result<String, Box<dyn Error>> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    let (command, args) = match words.split_first() {
+        Some((command, args)) => (*command, args),
+        None => return Ok(String::new()),
+    };
+
+    match (command, args) {
+        ("help", []) => Ok(HELP.to_string()),
+        ("fill", [material, x0, y0, x1, y1]) => {
+            let strain = world
+                .materials()
+                .find(material)
+                .ok_or_else(|| format!("no material `{}`", material))?;
+            let filled = fill(
+                world,
+                strain,
+                (number(x0)?, number(y0)?),
+                (number(x1)?, number(y1)?),
+            );
+            Ok(format!("filled {} cells", filled))
+        }
+        ("clear", []) => {
+            let (width, height) = (world.width() as isize, world.height() as isize);
+            fill(world, Strain::EMPTY, (0, 0), (width - 1, height - 1));
+            Ok(String::new())
+        }
+        ("seed", [seed]) => {
+            world.reseed(number(seed)?);
+            Ok(format!("seed is now {}", world.seed()))
+        }
+        ("save", [path]) => {
+            crate::save_world(world, path)?;
+            Ok(format!("saved to {}", path))
+        }
+        ("load", [path]) => {
+            world.load_into(path)?;
+            Ok(format!("loaded {}", path))
+        }
+        ("set", [setting, value]) => match *setting {
+            "gravity" => {
+                let gravity: f32 = number(value)?;
+                if !gravity.is_finite() {
+                    return Err(format!("`{}` is not a finite number", value).into());
+                }
+
+                world.set_gravity(gravity);
+                if gravity == 0.0 {
+                    Ok("gravity is now 0, things fall at a steady 1 cell per tick".to_string())
+                } else {
+                    Ok(format!("gravity is now {}", world.gravity()))
+                }
+            }
+            _ => Err(format!("unknown setting `{}`", setting).into()),
+        },
+        ("help" | "fill" | "clear" | "seed" | "save" | "load" | "set", _) => {
+            Err(format!("wrong arguments for `{}`, try `help`", command).into())
+        }
+        _ => Err(format!("unknown command `{}`, try `help`", command).into()),
+    }
+}
+
+/// The file a line loads a snapshot from, if it is a `load` command.
+pub fn load_path(line: &str) -> Option<&str> {
+    match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["load", path] => Some(path),
+        _ => None,
+    }
+}
+
+fn number<T: FromStr>(word: &str) -> Result<T, String> {
+    word.parse()
+        .map_err(|_| format!("`{}` is not a valid number", word))
+}
+
+// Replaces every cell between two corners with fresh particles of `strain`, returning how many
+// were inside the grid
+fn fill(world: &mut World, strain: Strain, from: (isize, isize), to: (isize, isize)) -> usize {
+    let clip = |a: isize, b: isize, size: usize| a.min(b).max(0)..=a.max(b).min(size as isize - 1);
+    let xs = clip(from.0, to.0, world.width());
+    let ys = clip(from.1, to.1, world.height());
+
+    for y in ys.clone() {
+        for x in xs.clone() {
+            let p = world.create_particle(strain);
+            world.set(x as usize, y as usize, p);
+        }
+    }
+
+    xs.count() * ys.count()
+}
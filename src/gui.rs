@@ -0,0 +1,951 @@
+use std::collections::HashSet;
+use std::fs;
+use std::sync::{Arc, OnceLock};
+
+use coffee::graphics::{
+    Batch, Color, Font, Frame, Image, Mesh, Point, Rectangle, Shape, Sprite, Target, Text, Window,
+    WindowSettings,
+};
+use coffee::input::{keyboard, mouse, Input};
+use coffee::load::{loading_screen::ProgressBar, Join, Task};
+use coffee::{input, Game, Result, Timer};
+use falling_sand_rs::replay::Command;
+#[cfg(feature = "scripting")]
+use falling_sand_rs::Script;
+use falling_sand_rs::{
+    Brush, Colour, Drawing, History, Materials, Particle, Recorder, Strain, Tool, ToolInput, World,
+};
+use rayon::prelude::*;
+
+use crate::{console, Settings};
+
+// Options chosen on the command line, set before the game is constructed
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+// Keys that select a material, by material id
+const HOTKEYS: [(keyboard::KeyCode, &str); 9] = [
+    (keyboard::KeyCode::E, "empty"),
+    (keyboard::KeyCode::Key1, "sand"),
+    (keyboard::KeyCode::Key2, "water"),
+    (keyboard::KeyCode::Key3, "wood"),
+    (keyboard::KeyCode::Key4, "fire"),
+    (keyboard::KeyCode::Key5, "oil_crude"),
+    (keyboard::KeyCode::Key6, "ice"),
+    (keyboard::KeyCode::Key7, "steam"),
+    (keyboard::KeyCode::Key8, "smoke"),
+];
+
+// Simulation speeds to choose from, as multiples of one step per update
+const SPEEDS: [f32; 6] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED: usize = 2;
+
+// Layout of the material palette down the right of the window, in pixels
+const PALETTE_WIDTH: f32 = 136.0;
+const PALETTE_ROW: f32 = 18.0;
+const SWATCH_SIZE: f32 = 14.0;
+
+/// Opens the window and runs the interactive simulation until it is closed.
+pub fn run(settings: Settings) -> Result<()> {
+    let settings = SETTINGS.get_or_init(|| settings);
+
+    FallingSand::run(WindowSettings {
+        title: String::from("Falling Sand - brewed with Coffee!"),
+        size: (
+            (settings.grid_width as f32 * settings.scale) as u32,
+            (settings.grid_height as f32 * settings.scale) as u32,
+        ),
+        resizable: false,
+        fullscreen: false,
+        maximized: false,
+    })
+}
+
+struct Inputs {
+    cursor_position: Point,
+    mouse_wheel: Point,
+    keys_pressed: HashSet<keyboard::KeyCode>,
+    mouse_buttons_pressed: HashSet<mouse::Button>,
+    text_buffer: String,
+}
+
+impl Input for Inputs {
+    fn new() -> Inputs {
+        Inputs {
+            cursor_position: Point::new(0.0, 0.0),
+            mouse_wheel: Point::new(0.0, 0.0),
+            keys_pressed: HashSet::new(),
+            mouse_buttons_pressed: HashSet::new(),
+            text_buffer: String::new(),
+        }
+    }
+
+    fn update(&mut self, event: input::Event) {
+        match event {
+            input::Event::Mouse(mouse_event) => match mouse_event {
+                mouse::Event::CursorMoved { x, y } => {
+                    self.cursor_position = Point::new(x, y);
+                }
+                mouse::Event::Input { state, button } => match state {
+                    input::ButtonState::Pressed => {
+                        self.mouse_buttons_pressed.insert(button);
+                    }
+                    input::ButtonState::Released => {
+                        self.mouse_buttons_pressed.remove(&button);
+                    }
+                },
+                mouse::Event::WheelScrolled { delta_x, delta_y } => {
+                    self.mouse_wheel =
+                        Point::new(self.mouse_wheel.x + delta_x, self.mouse_wheel.y + delta_y);
+                }
+                _ => {}
+            },
+            input::Event::Keyboard(keyboard_event) => match keyboard_event {
+                keyboard::Event::TextEntered { character } => {
+                    self.text_buffer.push(character);
+                }
+                keyboard::Event::Input { key_code, state } => match state {
+                    input::ButtonState::Pressed => {
+                        self.keys_pressed.insert(key_code);
+                    }
+                    input::ButtonState::Released => {
+                        self.keys_pressed.remove(&key_code);
+                    }
+                },
+            },
+            _ => {}
+        }
+    }
+
+    fn clear(&mut self) {
+        self.text_buffer.clear();
+        self.mouse_wheel = Point::new(0.0, 0.0);
+    }
+}
+
+struct FallingSand {
+    font: Font,
+    world: World,
+    batch: Batch,
+    cursor_position: Point,
+    mouse_wheel: Point,
+    keys_pressed: HashSet<keyboard::KeyCode>,
+    keys_just_pressed: HashSet<keyboard::KeyCode>,
+    mouse_buttons_pressed: HashSet<mouse::Button>,
+    text_buffer: String,
+    active_strain: Strain,
+    brush: Brush,
+    tool: Tool,
+    drawing: Drawing,
+    // the region the fill tool would fill from a cell, as spans of cells along rows, kept until
+    // the cursor moves or the grid changes
+    fill_preview: Option<((isize, isize), Vec<Span>)>,
+    history: History,
+    // where the session is being recorded to, if it is
+    recorder: Option<Recorder>,
+    scale: f32,
+    // outline the chunks that are being simulated
+    show_chunks: bool,
+    show_palette: bool,
+    // the left button went down on the palette, so it isn't painting
+    picking: bool,
+    console_open: bool,
+    console_log: Vec<String>,
+    paused: bool,
+    // index into SPEEDS
+    speed: usize,
+    // fractions of a step carried over between updates at speeds below 1x
+    steps_owed: f32,
+    // run after every step, until it fails
+    #[cfg(feature = "scripting")]
+    script: Option<Script>,
+}
+
+impl FallingSand {
+    const MAX_TEXTSIZE: usize = 40;
+    // lines of console output kept on screen
+    const CONSOLE_LINES: usize = 6;
+    const QUICKSAVE_PATH: &'static str = "quicksave.bin";
+    const SCREENSHOT_PATH: &'static str = "screenshot.png";
+
+    fn new(batch: Batch, font: Font, world: World, scale: f32, undo_budget: usize) -> FallingSand {
+        let active_strain = world.materials().find("sand").unwrap_or(Strain::EMPTY);
+
+        FallingSand {
+            font,
+            world,
+            batch,
+            cursor_position: Point::new(0.0, 0.0),
+            mouse_wheel: Point::new(0.0, 0.0),
+            keys_pressed: HashSet::new(),
+            keys_just_pressed: HashSet::new(),
+            mouse_buttons_pressed: HashSet::new(),
+            text_buffer: String::with_capacity(Self::MAX_TEXTSIZE),
+            active_strain,
+            brush: Brush::default(),
+            tool: Tool::Paint,
+            drawing: Drawing::default(),
+            fill_preview: None,
+            history: History::new(undo_budget),
+            recorder: None,
+            scale,
+            show_chunks: false,
+            show_palette: true,
+            picking: false,
+            console_open: false,
+            console_log: Vec::new(),
+            paused: false,
+            speed: NORMAL_SPEED,
+            steps_owed: 0.0,
+            #[cfg(feature = "scripting")]
+            script: None,
+        }
+    }
+
+    fn load() -> Task<FallingSand> {
+        let settings = SETTINGS.get().expect("settings are parsed in main");
+        let mut world = World::with_materials(
+            settings.grid_width,
+            settings.grid_height,
+            rand::random(),
+            Arc::clone(&settings.materials),
+        );
+        #[cfg(feature = "scripting")]
+        let script = settings
+            .script
+            .as_ref()
+            .map(|path| Script::load(path, &mut world).map_err(|e| format!("{}: {}", path, e)));
+        let recorder = settings.record.as_ref().map(|path| {
+            Recorder::create(path, &world, settings.undo_budget)
+                .map_err(|e| format!("{}: {}", path, e))
+        });
+        let scale = settings.scale;
+        let undo_budget = settings.undo_budget;
+        let palette = palette(world.materials());
+
+        (
+            Task::using_gpu(move |gpu| Image::from_colors(gpu, &palette)),
+            Font::load_from_bytes(include_bytes!("../resources/Gamepixies-8MO6n.ttf")),
+        )
+            .join()
+            .map(move |(palette, font)| {
+                let mut game =
+                    FallingSand::new(Batch::new(palette), font, world, scale, undo_budget);
+
+                // a broken script or recording is reported in the console rather than stopping
+                // the game
+                #[cfg(feature = "scripting")]
+                match script {
+                    Some(Ok(script)) => game.script = Some(script),
+                    Some(Err(e)) => {
+                        game.log(format!("error: {}", e));
+                        game.console_open = true;
+                    }
+                    None => {}
+                }
+                match recorder {
+                    Some(Ok(recorder)) => game.recorder = Some(recorder),
+                    Some(Err(e)) => {
+                        game.log(format!("error: {}", e));
+                        game.console_open = true;
+                    }
+                    None => {}
+                }
+
+                game
+            })
+    }
+
+    // the grid cell under a point on screen
+    fn cell_at(&self, point: Point) -> (usize, usize) {
+        (
+            (point.x / self.scale) as usize,
+            (point.y / self.scale) as usize,
+        )
+    }
+
+    // where a material's row of the palette is on screen, by index, against the right edge or
+    // the left one in a window too narrow to hold it
+    fn palette_row(&self, i: usize) -> Rectangle<f32> {
+        Rectangle {
+            x: (self.world.width() as f32 * self.scale - PALETTE_WIDTH).max(0.0),
+            y: 2.0 + i as f32 * PALETTE_ROW,
+            width: PALETTE_WIDTH - 4.0,
+            height: PALETTE_ROW,
+        }
+    }
+
+    // the material whose palette row is under a point on screen
+    fn palette_at(&self, point: Point) -> Option<Strain> {
+        if !self.show_palette {
+            return None;
+        }
+
+        self.world
+            .materials()
+            .strains()
+            .enumerate()
+            .find_map(|(i, strain)| {
+                let row = self.palette_row(i);
+                let inside = point.x >= row.x
+                    && point.x < row.x + row.width
+                    && point.y >= row.y
+                    && point.y < row.y + row.height;
+
+                inside.then_some(strain)
+            })
+    }
+
+    // every material's swatch and name, with the active one outlined
+    fn draw_palette(&mut self, target: &mut Target) {
+        let materials = Arc::clone(self.world.materials());
+        let mut outline = Mesh::new();
+
+        self.batch.clear();
+
+        for (i, strain) in materials.strains().enumerate() {
+            let row = self.palette_row(i);
+
+            self.batch.add(Sprite {
+                source: Rectangle {
+                    x: strain.id() as u16,
+                    y: 0,
+                    width: 1,
+                    height: 1,
+                },
+                position: Point::new(row.x + 2.0, row.y + 2.0),
+                scale: (SWATCH_SIZE, SWATCH_SIZE),
+            });
+
+            self.font.add(Text {
+                content: &materials[strain].name,
+                position: Point::new(row.x + SWATCH_SIZE + 6.0, row.y + 1.0),
+                size: 16.0,
+                color: Color::WHITE,
+                ..Text::default()
+            });
+
+            if strain == self.active_strain {
+                outline.stroke(Shape::Rectangle(row), Color::WHITE, 1.0);
+            }
+        }
+
+        let mut background = Mesh::new();
+        let first = self.palette_row(0);
+        background.fill(
+            Shape::Rectangle(Rectangle {
+                height: materials.len() as f32 * PALETTE_ROW,
+                ..first
+            }),
+            Color::new(0.0, 0.0, 0.0, 0.6),
+        );
+
+        background.draw(target);
+        self.batch.draw(target);
+        outline.draw(target);
+    }
+
+    // the console's recent output and the line being typed, along the bottom of the window
+    fn draw_console(&mut self, target: &mut Target) {
+        let width = self.world.width() as f32 * self.scale;
+        let height = self.world.height() as f32 * self.scale;
+        let line_height = 14.0;
+        let top = height - (Self::CONSOLE_LINES + 1) as f32 * line_height - 4.0;
+
+        let mut background = Mesh::new();
+        background.fill(
+            Shape::Rectangle(Rectangle {
+                x: 0.0,
+                y: top,
+                width,
+                height: height - top,
+            }),
+            Color::new(0.0, 0.0, 0.0, 0.75),
+        );
+        background.draw(target);
+
+        let prompt = format!("> {}_", self.text_buffer);
+        let lines = self
+            .console_log
+            .iter()
+            .map(String::as_str)
+            .chain([prompt.as_str()]);
+
+        for (i, line) in lines.enumerate() {
+            self.font.add(Text {
+                content: line,
+                position: Point::new(8.0, top + 2.0 + i as f32 * line_height),
+                size: 16.0,
+                color: Color::WHITE,
+                ..Text::default()
+            });
+        }
+    }
+
+    // steps the active material through the palette, wrapping around at either end
+    fn cycle_strain(&mut self, by: isize) {
+        let count = self.world.materials().len() as isize;
+        let id = (self.active_strain.id() as isize + by).rem_euclid(count);
+
+        if let Some(strain) = self.world.materials().strain(id as u8) {
+            self.active_strain = strain;
+        }
+    }
+
+    fn cursor_cell(&self) -> (isize, isize) {
+        let (x, y) = self.cell_at(self.cursor_position);
+        (x as isize, y as isize)
+    }
+
+    // What the current tool would draw if the mouse was released now, in the active material's
+    // colour. Lines and rectangles show while dragged out, fill shows the region under the cursor.
+    fn preview(&mut self) -> Mesh {
+        let mut mesh = Mesh::new();
+        let scale = self.scale;
+        let colour = self.world.materials()[self.active_strain].colour;
+        let color = Color::new(colour.r, colour.g, colour.b, 0.5);
+        let to = self.cursor_cell();
+
+        match (self.tool, self.drawing.drag_from()) {
+            (Tool::Line, Some(from)) => {
+                let centre = |(x, y): (isize, isize)| {
+                    Point::new((x as f32 + 0.5) * scale, (y as f32 + 0.5) * scale)
+                };
+
+                mesh.stroke(
+                    Shape::Polyline {
+                        points: vec![centre(from), centre(to)],
+                    },
+                    color,
+                    (self.brush.radius * 2 + 1) as f32 * scale,
+                );
+            }
+            (Tool::Rect { filled }, Some(from)) => {
+                let rect = Rectangle {
+                    x: from.0.min(to.0) as f32 * scale,
+                    y: from.1.min(to.1) as f32 * scale,
+                    width: ((from.0 - to.0).abs() + 1) as f32 * scale,
+                    height: ((from.1 - to.1).abs() + 1) as f32 * scale,
+                };
+
+                if filled {
+                    mesh.fill(Shape::Rectangle(rect), color);
+                } else {
+                    mesh.stroke(Shape::Rectangle(rect), color, scale);
+                }
+            }
+            (Tool::Fill, _) => {
+                if self.fill_preview.as_ref().map(|(cell, _)| *cell) != Some(to) {
+                    self.fill_preview = Some((to, fill_spans(&self.world, to)));
+                }
+
+                for span in self.fill_preview.iter().flat_map(|(_, spans)| spans) {
+                    mesh.fill(
+                        Shape::Rectangle(Rectangle {
+                            x: span.x as f32 * scale,
+                            y: span.y as f32 * scale,
+                            width: span.width as f32 * scale,
+                            height: scale,
+                        }),
+                        color,
+                    );
+                }
+            }
+            _ => {}
+        }
+
+        mesh
+    }
+
+    // Applies the current tool for this tick's mouse button state, returning what it was used
+    // with
+    fn use_tool(&mut self, left_down: bool) -> ToolInput {
+        let input = ToolInput {
+            cursor: self.cursor_cell(),
+            down: left_down,
+            tool: self.tool,
+            brush: self.brush,
+            strain: self.active_strain,
+        };
+
+        // tools only edit while the button is down or as it is released
+        if left_down || self.drawing.was_down() {
+            self.fill_preview = None;
+        }
+
+        self.drawing
+            .update(&mut self.world, &mut self.history, &input);
+        input
+    }
+
+    // Runs a command and adds it to the recording
+    fn command(&mut self, command: Command) {
+        self.fill_preview = None;
+
+        match &command {
+            Command::Undo => {
+                self.history.undo(&mut self.world);
+            }
+            Command::Redo => {
+                self.history.redo(&mut self.world);
+            }
+            Command::Console(line) => {
+                self.history.record(&self.world);
+
+                self.log(format!("> {}", line));
+                match console::run(&mut self.world, line) {
+                    Ok(output) if output.is_empty() => {}
+                    Ok(output) => self.log(output),
+                    Err(e) => self.log(format!("error: {}", e)),
+                }
+            }
+            Command::Load(snapshot) => {
+                self.history.record(&self.world);
+
+                if let Err(e) = self.world.read_into(&mut &snapshot[..]) {
+                    self.log(format!("error: {}", e));
+                }
+            }
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            // a load is recorded as the snapshot it loaded, so playback doesn't need the file
+            let command = match command {
+                Command::Console(line) => match console::load_path(&line).map(fs::read) {
+                    Some(Ok(snapshot)) => Command::Load(snapshot),
+                    _ => Command::Console(line),
+                },
+                command => command,
+            };
+            recorder.command(command);
+        }
+    }
+
+    // keyboard shortcuts, for while the console is closed
+    fn hotkeys(&mut self) {
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::Grave) {
+            self.console_open = true;
+            self.text_buffer.clear();
+            return;
+        }
+
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::F2) {
+            self.screenshot();
+        }
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::F5) {
+            self.quicksave();
+        }
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::F9) {
+            self.quickload();
+        }
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::F3) {
+            self.show_chunks = !self.show_chunks;
+        }
+
+        let ctrl = self.keys_pressed.contains(&keyboard::KeyCode::LControl)
+            || self.keys_pressed.contains(&keyboard::KeyCode::RControl);
+        if ctrl && self.keys_just_pressed.contains(&keyboard::KeyCode::Z) {
+            self.command(Command::Undo);
+        }
+        if ctrl && self.keys_just_pressed.contains(&keyboard::KeyCode::Y) {
+            self.command(Command::Redo);
+        }
+
+        // Update current strain for mouse click
+        let keys_pressed = &self.keys_pressed;
+        let x: Option<&(keyboard::KeyCode, &str)> = HOTKEYS
+            .par_iter()
+            .find_first(|(key, _)| keys_pressed.contains(key));
+
+        if let Some(strain) = x.and_then(|(_, id)| self.world.materials().find(id)) {
+            self.active_strain = strain;
+        }
+
+        // B cycles the brush shape and the mouse wheel sizes it
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::B) {
+            self.brush.shape = self.brush.shape.next();
+        }
+        if self.mouse_wheel.y != 0.0 {
+            self.brush.resize(self.mouse_wheel.y.signum() as isize);
+        }
+
+        // P paints, L draws lines, R rectangles (again to toggle filled) and F fills
+        let tool = if self.keys_just_pressed.contains(&keyboard::KeyCode::P) {
+            Some(Tool::Paint)
+        } else if self.keys_just_pressed.contains(&keyboard::KeyCode::L) {
+            Some(Tool::Line)
+        } else if self.keys_just_pressed.contains(&keyboard::KeyCode::R) {
+            Some(match self.tool {
+                Tool::Rect { filled } => Tool::Rect { filled: !filled },
+                _ => Tool::Rect { filled: false },
+            })
+        } else if self.keys_just_pressed.contains(&keyboard::KeyCode::F) {
+            Some(Tool::Fill)
+        } else {
+            None
+        };
+
+        if let Some(tool) = tool {
+            self.tool = tool;
+        }
+
+        // [ and ] step through every material, Tab shows or hides the palette
+        if self
+            .keys_just_pressed
+            .contains(&keyboard::KeyCode::LBracket)
+        {
+            self.cycle_strain(-1);
+        }
+        if self
+            .keys_just_pressed
+            .contains(&keyboard::KeyCode::RBracket)
+        {
+            self.cycle_strain(1);
+        }
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::Tab) {
+            self.show_palette = !self.show_palette;
+        }
+
+        // Space pauses, N steps once while paused, - and = slow down and speed up
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::Space) {
+            self.paused = !self.paused;
+            self.steps_owed = 0.0;
+        }
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::Minus) {
+            self.speed = self.speed.saturating_sub(1);
+        }
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::Equals) {
+            self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+        }
+
+        if self.paused && self.keys_just_pressed.contains(&keyboard::KeyCode::N) {
+            self.steps_owed += 1.0;
+        }
+    }
+
+    // Enter runs the typed command, Escape or ` closes the console
+    fn console_keys(&mut self) {
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::Return) {
+            let line = std::mem::take(&mut self.text_buffer);
+            self.command(Command::Console(line));
+        }
+
+        if self.keys_just_pressed.contains(&keyboard::KeyCode::Escape)
+            || self.keys_just_pressed.contains(&keyboard::KeyCode::Grave)
+        {
+            self.console_open = false;
+        }
+    }
+
+    // Adds a line to the console, forgetting the oldest once there are too many to show
+    fn log(&mut self, line: String) {
+        self.console_log.push(line);
+
+        let excess = self.console_log.len().saturating_sub(Self::CONSOLE_LINES);
+        self.console_log.drain(..excess);
+    }
+
+    // Steps the world, then runs the script's hooks for the new tick
+    fn step(&mut self) {
+        self.world.step();
+        self.fill_preview = None;
+
+        #[cfg(feature = "scripting")]
+        if let Some(script) = self.script.as_mut() {
+            if let Err(e) = script.tick(&mut self.world) {
+                self.script = None;
+                self.log(format!("script stopped: {}", e));
+                self.console_open = true;
+            }
+        }
+    }
+
+    fn quicksave(&mut self) {
+        match self.world.save(Self::QUICKSAVE_PATH) {
+            Ok(()) => self.log(format!("saved to {}", Self::QUICKSAVE_PATH)),
+            Err(e) => {
+                self.log(format!("error: {}: {}", Self::QUICKSAVE_PATH, e));
+                self.console_open = true;
+            }
+        }
+    }
+
+    fn screenshot(&mut self) {
+        match self.world.to_png(Self::SCREENSHOT_PATH) {
+            Ok(()) => self.log(format!("saved screenshot to {}", Self::SCREENSHOT_PATH)),
+            Err(e) => {
+                self.log(format!("error: {}: {}", Self::SCREENSHOT_PATH, e));
+                self.console_open = true;
+            }
+        }
+    }
+
+    // the same as loading the quicksave from the console, so it can be recorded as that
+    fn quickload(&mut self) {
+        self.command(Command::Console(format!("load {}", Self::QUICKSAVE_PATH)));
+    }
+}
+
+impl Game for FallingSand {
+    type Input = Inputs;
+    type LoadingScreen = ProgressBar;
+
+    const TICKS_PER_SECOND: u16 = 60;
+
+    fn load(_window: &Window) -> Task<Self> {
+        Task::stage("Loading...", FallingSand::load())
+    }
+
+    fn draw(&mut self, frame: &mut Frame, _timer: &Timer) {
+        frame.clear(Color::BLACK);
+
+        let target = &mut frame.as_target();
+
+        let scale = self.scale;
+
+        self.batch.clear();
+
+        // collects all particles into a batch of rectangle sprites
+        for x in 0..self.world.width() {
+            for y in 0..self.world.height() {
+                let p = self.world.get(x, y);
+                if p.strain != Strain::EMPTY {
+                    self.batch.add(Sprite {
+                        source: Rectangle {
+                            x: p.strain.id() as u16,
+                            y: 0,
+                            width: 1,
+                            height: 1,
+                        },
+                        position: Point::new(x as f32 * scale, y as f32 * scale),
+                        scale: (1.0 * scale, 1.0 * scale),
+                    });
+                }
+            }
+        }
+
+        // draw particle batch
+        self.batch.draw(target);
+
+        if self.show_chunks {
+            let mut mesh = Mesh::new();
+
+            for chunk in self.world.chunks().filter(|c| c.awake) {
+                mesh.stroke(
+                    Shape::Rectangle(Rectangle {
+                        x: chunk.x as f32 * scale,
+                        y: chunk.y as f32 * scale,
+                        width: chunk.width as f32 * scale,
+                        height: chunk.height as f32 * scale,
+                    }),
+                    Color::new(1.0, 0.0, 1.0, 0.8),
+                    1.0,
+                );
+            }
+
+            if !mesh.is_empty() {
+                mesh.draw(target);
+            }
+        }
+
+        let preview = self.preview();
+        if !preview.is_empty() {
+            preview.draw(target);
+        }
+
+        if self.show_palette {
+            self.draw_palette(target);
+        }
+
+        // add and then draw text
+        self.font.add(Text {
+            content: &format!("particles_updated={}", self.world.particles_updated()),
+            position: Point::new(8.0, 2.0),
+            size: 16.0,
+            color: Color::WHITE,
+            ..Text::default()
+        });
+
+        let (cur_x, cur_y) = self.cell_at(self.cursor_position);
+
+        let materials = self.world.materials();
+
+        let under_cur = if self.world.in_bounds(cur_x, cur_y) {
+            self.world.get(cur_x, cur_y)
+        } else {
+            Particle::default()
+        };
+
+        self.font.add(Text {
+            content: &format!(
+                "under cursor: {} ({:.0} deg)",
+                materials[under_cur.strain].name, under_cur.temperature
+            ),
+            position: Point::new(8., 16.),
+            size: 16.0,
+            color: Color::WHITE,
+            ..Text::default()
+        });
+
+        self.font.add(Text {
+            content: &format!("active: {}", materials[self.active_strain].name),
+            position: Point::new(8., 30.),
+            size: 16.0,
+            color: to_color(materials[self.active_strain].colour),
+            ..Text::default()
+        });
+
+        self.font.add(Text {
+            content: &format!(
+                "tool: {}, brush: {} {}",
+                self.tool.name(),
+                self.brush.shape.name(),
+                self.brush.radius
+            ),
+            position: Point::new(8., 44.),
+            size: 16.0,
+            color: Color::WHITE,
+            ..Text::default()
+        });
+
+        self.font.add(Text {
+            content: &if self.paused {
+                String::from("paused")
+            } else {
+                format!("speed: {}x", SPEEDS[self.speed])
+            },
+            position: Point::new(8., 58.),
+            size: 16.0,
+            color: Color::WHITE,
+            ..Text::default()
+        });
+
+        if self.console_open {
+            self.draw_console(target);
+        }
+
+        self.font.draw(target);
+    }
+
+    //noinspection RsBorrowChecker
+    fn interact(&mut self, input: &mut Inputs, _window: &mut Window) {
+        self.cursor_position = input.cursor_position;
+        self.mouse_wheel = input.mouse_wheel;
+        self.keys_just_pressed = input
+            .keys_pressed
+            .difference(&self.keys_pressed)
+            .copied()
+            .collect();
+        self.keys_pressed = input.keys_pressed.clone();
+        self.mouse_buttons_pressed = input.mouse_buttons_pressed.clone();
+
+        if self.console_open {
+            for c in input.text_buffer.chars() {
+                match c {
+                    // Match ASCII backspace and delete from the text buffer
+                    '\u{0008}' => {
+                        self.text_buffer.pop();
+                    }
+                    // enter and the key that opens the console are handled as key presses
+                    '`' => {}
+                    c if c.is_control() => {}
+                    _ => {
+                        if self.text_buffer.chars().count() < Self::MAX_TEXTSIZE {
+                            self.text_buffer.push(c);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn update(&mut self, _window: &Window) {
+        // while the console is open, typing goes to it instead of the hotkeys
+        if self.console_open {
+            self.console_keys();
+        } else {
+            self.hotkeys();
+        }
+
+        let left_down = self.mouse_buttons_pressed.contains(&mouse::Button::Left);
+
+        // clicking the palette picks a material instead of using the tool
+        if left_down && !self.drawing.was_down() {
+            if let Some(strain) = self.palette_at(self.cursor_position) {
+                self.active_strain = strain;
+                self.picking = true;
+            }
+        }
+        if !left_down {
+            self.picking = false;
+        }
+
+        let input = self.use_tool(left_down && !self.picking);
+
+        // slower speeds skip updates and faster ones step several times
+        if !self.paused {
+            self.steps_owed += SPEEDS[self.speed];
+        }
+        let mut steps = 0;
+        while self.steps_owed >= 1.0 {
+            self.step();
+            self.steps_owed -= 1.0;
+            steps += 1;
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(e) = recorder.frame(&input, steps, &self.world) {
+                self.recorder = None;
+                self.log(format!("recording stopped: {}", e));
+                self.console_open = true;
+            }
+        }
+
+        // handled, so a second update before the next input doesn't repeat them
+        self.keys_just_pressed.clear();
+        self.mouse_wheel = Point::new(0.0, 0.0);
+    }
+
+    fn on_close_request(&mut self) -> bool {
+        if let Some(recorder) = self.recorder.take() {
+            if let Err(e) = recorder.finish(&self.world) {
+                eprintln!("Failed to finish the recording: {}", e);
+            }
+        }
+        true
+    }
+
+    const DEBUG_KEY: Option<keyboard::KeyCode> = Some(keyboard::KeyCode::F12);
+}
+
+// a run of cells along a row
+struct Span {
+    x: usize,
+    y: usize,
+    width: usize,
+}
+
+// The region the fill tool would fill from `cell`, joined up into spans along each row so it can
+// be drawn with a rectangle per span rather than per cell
+fn fill_spans(world: &World, cell: (isize, isize)) -> Vec<Span> {
+    let mut region = world.connected_region(cell.0 as usize, cell.1 as usize);
+    region.sort_unstable_by_key(|&(x, y)| (y, x));
+
+    let mut spans: Vec<Span> = Vec::new();
+    for (x, y) in region {
+        match spans.last_mut() {
+            Some(span) if span.y == y && span.x + span.width == x => span.width += 1,
+            _ => spans.push(Span { x, y, width: 1 }),
+        }
+    }
+
+    spans
+}
+
+fn to_color(c: Colour) -> Color {
+    Color::new(c.r, c.g, c.b, c.a)
+}
+
+// one colour per material, indexed by strain id
+fn palette(materials: &Materials) -> Vec<Color> {
+    materials.iter().map(|m| to_color(m.colour)).collect()
+}
@@ -0,0 +1,29 @@
+//! 64 bit FNV-1a, for hashes that must come out the same on every machine and every run.
+
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn write_f32(&mut self, f: f32) {
+        self.write(&f.to_bits().to_le_bytes());
+    }
+
+    // length first, so neighbouring strings can't run into each other
+    pub(crate) fn write_str(&mut self, s: &str) {
+        self.write(&(s.len() as u32).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,228 @@
+//! Undo and redo for edits made to a world.
+//!
+//! Only the most recent checkpoint is kept as a whole grid. Every other is stored as the cells
+//! that changed between it and its neighbour, in runs of consecutive cells that hold each
+//! repeated particle once, so a history of small edits or large fills on a big grid stays
+//! small. The oldest checkpoints are forgotten once the
+//! history grows past its memory budget.
+
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use crate::particle::{same_state, Particle};
+use crate::world::World;
+
+// The cells that differ between two checkpoints, as runs of consecutive cells. Only one side is
+// kept: what was there in whichever checkpoint the grid isn't at. Swapping it with the grid's
+// cells moves between the two, and leaves the diff holding the side that was swapped out.
+struct Diff {
+    runs: Vec<Run>,
+}
+
+struct Run {
+    start: usize,
+    len: usize,
+    // the run's cells, with repeats of the same particle stored once
+    cells: Vec<(u32, Particle)>,
+}
+
+// Adds `p` to the end of `cells`, as a repeat of the last particle if it is the same
+fn push(cells: &mut Vec<(u32, Particle)>, p: Particle) {
+    match cells.last_mut() {
+        Some((n, last)) if same_state(last, &p) => *n += 1,
+        _ => cells.push((1, p)),
+    }
+}
+
+impl Diff {
+    // the diff from `before` to `after`, to be swapped with a grid holding `after`
+    fn between(before: &[Particle], after: &[Particle]) -> Diff {
+        let mut runs: Vec<Run> = Vec::new();
+
+        for (i, (b, a)) in before.iter().zip(after.iter()).enumerate() {
+            if same_state(b, a) {
+                continue;
+            }
+
+            match runs.last_mut() {
+                Some(run) if run.start + run.len == i => {
+                    run.len += 1;
+                    push(&mut run.cells, *b);
+                }
+                _ => runs.push(Run {
+                    start: i,
+                    len: 1,
+                    cells: vec![(1, *b)],
+                }),
+            }
+        }
+
+        Diff { runs }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    // roughly how many bytes it takes up
+    fn size(&self) -> usize {
+        self.runs
+            .iter()
+            .map(|r| size_of::<Run>() + r.cells.len() * size_of::<(u32, Particle)>())
+            .sum()
+    }
+
+    // turns one of its checkpoints in `cells` into the other
+    fn swap(&mut self, cells: &mut [Particle]) {
+        for run in self.runs.iter_mut() {
+            let to = &mut cells[run.start..run.start + run.len];
+
+            let mut from = Vec::new();
+            for p in to.iter() {
+                push(&mut from, *p);
+            }
+
+            let mut i = 0;
+            for &(n, p) in run.cells.iter() {
+                to[i..i + n as usize].fill(p);
+                i += n as usize;
+            }
+
+            run.cells = from;
+        }
+    }
+}
+
+/// A timeline of checkpoints of a world that can be stepped back and forth through.
+pub struct History {
+    budget: usize,
+    // the checkpoint the world was last recorded at or restored to
+    checkpoint: Option<Vec<Particle>>,
+    // diffs[i] lies between checkpoints i and i + 1, holding the cells of whichever of the two
+    // is further from `at`
+    diffs: VecDeque<Diff>,
+    // which checkpoint `checkpoint` is
+    at: usize,
+    diffs_size: usize,
+}
+
+impl History {
+    /// How many bytes a default history may hold, 64 MiB.
+    pub const DEFAULT_BUDGET: usize = 64 * 1024 * 1024;
+
+    /// An empty history that forgets its oldest checkpoints once it holds more than `budget`
+    /// bytes.
+    pub fn new(budget: usize) -> History {
+        History {
+            budget,
+            checkpoint: None,
+            diffs: VecDeque::new(),
+            at: 0,
+            diffs_size: 0,
+        }
+    }
+
+    /// Roughly how many bytes the history is holding.
+    pub fn memory_used(&self) -> usize {
+        self.diffs_size
+            + self
+                .checkpoint
+                .as_ref()
+                .map_or(0, |c| c.len() * size_of::<Particle>())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.at < self.diffs.len()
+    }
+
+    /// Remembers the world as it is now, to be returned to by [`History::undo`]. Call it before
+    /// every edit. Anything that was undone can no longer be redone.
+    pub fn record(&mut self, world: &World) {
+        let cells = world.cells();
+
+        let checkpoint = match self.checkpoint.as_mut() {
+            Some(checkpoint) if checkpoint.len() == cells.len() => checkpoint,
+            _ => {
+                // nothing to compare against, or a world of a different size
+                self.clear();
+                self.checkpoint = Some(cells.to_vec());
+                return;
+            }
+        };
+
+        for forgotten in self.diffs.drain(self.at..) {
+            self.diffs_size -= forgotten.size();
+        }
+
+        let diff = Diff::between(checkpoint, cells);
+        if diff.is_empty() {
+            return;
+        }
+
+        self.diffs_size += diff.size();
+        self.diffs.push_back(diff);
+        self.at += 1;
+        checkpoint.copy_from_slice(cells);
+
+        while self.memory_used() > self.budget && !self.diffs.is_empty() {
+            let forgotten = self.diffs.pop_front().unwrap();
+            self.diffs_size -= forgotten.size();
+            self.at -= 1;
+        }
+    }
+
+    /// Puts the world back to how it was at the last checkpoint before its current state,
+    /// returning whether there was one. The state being left can be returned to with
+    /// [`History::redo`].
+    pub fn undo(&mut self, world: &mut World) -> bool {
+        if !self.can_redo() {
+            self.record(world);
+        }
+
+        match self.checkpoint.as_mut() {
+            Some(checkpoint) if self.at > 0 => {
+                self.at -= 1;
+                swap(&mut self.diffs[self.at], checkpoint, &mut self.diffs_size);
+                world.restore(checkpoint);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Steps forward again to the checkpoint that was last undone, returning whether there was
+    /// one.
+    pub fn redo(&mut self, world: &mut World) -> bool {
+        match self.checkpoint.as_mut() {
+            Some(checkpoint) if self.at < self.diffs.len() => {
+                swap(&mut self.diffs[self.at], checkpoint, &mut self.diffs_size);
+                self.at += 1;
+                world.restore(checkpoint);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Forgets every checkpoint.
+    pub fn clear(&mut self) {
+        self.checkpoint = None;
+        self.diffs.clear();
+        self.at = 0;
+        self.diffs_size = 0;
+    }
+}
+
+// swaps `diff` with the checkpoint, keeping `size` up to date since the side swapped out may
+// compress differently
+fn swap(diff: &mut Diff, checkpoint: &mut [Particle], size: &mut usize) {
+    *size -= diff.size();
+    diff.swap(checkpoint);
+    *size += diff.size();
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new(History::DEFAULT_BUDGET)
+    }
+}
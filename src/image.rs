@@ -0,0 +1,199 @@
+//! PNG import and export at one pixel per cell.
+//!
+//! Each strain is drawn in its material's colour, except `empty` which is black to match the
+//! renderer's background. Fully transparent pixels also import as `empty`.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+use std::sync::Arc;
+
+use png::{BitDepth, ColorType, Transformations};
+
+use crate::material::Materials;
+use crate::particle::Strain;
+use crate::world::World;
+
+const EMPTY_RGB: [u8; 3] = [0, 0, 0];
+
+#[derive(Debug)]
+pub enum PngError {
+    Io(io::Error),
+    Decoding(png::DecodingError),
+    Encoding(png::EncodingError),
+    /// A colour type that cannot be expanded to 8-bit RGB(A).
+    UnsupportedFormat(ColorType, BitDepth),
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::Io(e) => write!(f, "{}", e),
+            PngError::Decoding(e) => write!(f, "{}", e),
+            PngError::Encoding(e) => write!(f, "{}", e),
+            PngError::UnsupportedFormat(colour, depth) => {
+                write!(f, "unsupported png format {:?} {:?}", colour, depth)
+            }
+        }
+    }
+}
+
+impl Error for PngError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PngError::Io(e) => Some(e),
+            PngError::Decoding(e) => Some(e),
+            PngError::Encoding(e) => Some(e),
+            PngError::UnsupportedFormat(..) => None,
+        }
+    }
+}
+
+impl From<io::Error> for PngError {
+    fn from(e: io::Error) -> Self {
+        PngError::Io(e)
+    }
+}
+
+impl From<png::DecodingError> for PngError {
+    fn from(e: png::DecodingError) -> Self {
+        PngError::Decoding(e)
+    }
+}
+
+impl From<png::EncodingError> for PngError {
+    fn from(e: png::EncodingError) -> Self {
+        PngError::Encoding(e)
+    }
+}
+
+/// A pixel whose colour had no exact palette entry and was matched to the nearest strain.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UnmappedPixel {
+    pub x: usize,
+    pub y: usize,
+    pub rgb: [u8; 3],
+    pub strain: Strain,
+}
+
+pub struct PngImport {
+    pub world: World,
+    pub unmapped: Vec<UnmappedPixel>,
+}
+
+fn strain_rgb(materials: &Materials, strain: Strain) -> [u8; 3] {
+    if strain == Strain::EMPTY {
+        return EMPTY_RGB;
+    }
+
+    let [r, g, b, _] = materials[strain].colour.to_rgba8();
+    [r, g, b]
+}
+
+// the strain drawn in the colour closest to `rgb`, and whether that colour is an exact match
+fn nearest_strain(materials: &Materials, rgb: [u8; 3]) -> (Strain, bool) {
+    let distance = |other: [u8; 3]| -> u32 {
+        rgb.iter()
+            .zip(other.iter())
+            .map(|(&a, &b)| (a as i32 - b as i32).pow(2) as u32)
+            .sum()
+    };
+
+    let (strain, d) = materials
+        .strains()
+        .map(|s| (s, distance(strain_rgb(materials, s))))
+        .min_by_key(|&(_, d)| d)
+        .unwrap();
+
+    (strain, d == 0)
+}
+
+impl World {
+    /// Builds a world from a png, one cell per pixel, matching each pixel to the built-in
+    /// material with the nearest colour. Pixels without an exact match are listed in the
+    /// returned report. The particles' starting lifetimes, like everything after, are drawn
+    /// from `seed`.
+    pub fn from_png<P: AsRef<Path>>(path: P, seed: u64) -> Result<PngImport, PngError> {
+        World::from_png_with_materials(path, seed, Materials::builtin())
+    }
+
+    pub fn from_png_with_materials<P: AsRef<Path>>(
+        path: P,
+        seed: u64,
+        materials: Arc<Materials>,
+    ) -> Result<PngImport, PngError> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(Transformations::EXPAND | Transformations::STRIP_16);
+
+        let (info, mut reader) = decoder.read_info()?;
+        let mut buf = vec![0; info.buffer_size()];
+        reader.next_frame(&mut buf)?;
+
+        let samples = match (info.color_type, info.bit_depth) {
+            (ColorType::Grayscale, BitDepth::Eight) => 1,
+            (ColorType::GrayscaleAlpha, BitDepth::Eight) => 2,
+            (ColorType::RGB, BitDepth::Eight) => 3,
+            (ColorType::RGBA, BitDepth::Eight) => 4,
+            (colour, depth) => return Err(PngError::UnsupportedFormat(colour, depth)),
+        };
+
+        let mut world = World::with_materials(
+            info.width as usize,
+            info.height as usize,
+            seed,
+            Arc::clone(&materials),
+        );
+        let mut unmapped = Vec::new();
+
+        for (y, line) in buf.chunks(info.line_size).enumerate() {
+            for (x, pixel) in line.chunks(samples).take(world.width()).enumerate() {
+                let (rgb, alpha) = match *pixel {
+                    [l] => ([l, l, l], 255),
+                    [l, a] => ([l, l, l], a),
+                    [r, g, b] => ([r, g, b], 255),
+                    [r, g, b, a] => ([r, g, b], a),
+                    _ => unreachable!(),
+                };
+
+                let strain = if alpha == 0 {
+                    Strain::EMPTY
+                } else {
+                    let (strain, exact) = nearest_strain(&materials, rgb);
+                    if !exact {
+                        unmapped.push(UnmappedPixel { x, y, rgb, strain });
+                    }
+                    strain
+                };
+
+                if strain != Strain::EMPTY {
+                    let p = world.create_particle(strain);
+                    world.set(x, y, p);
+                }
+            }
+        }
+
+        Ok(PngImport { world, unmapped })
+    }
+
+    /// Writes the raw grid to an RGB png at one pixel per cell.
+    pub fn to_png<P: AsRef<Path>>(&self, path: P) -> Result<(), PngError> {
+        let file = BufWriter::new(File::create(path)?);
+
+        let mut encoder = png::Encoder::new(file, self.width() as u32, self.height() as u32);
+        encoder.set_color(ColorType::RGB);
+        encoder.set_depth(BitDepth::Eight);
+
+        let mut data = Vec::with_capacity(self.width() * self.height() * 3);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                data.extend_from_slice(&strain_rgb(self.materials(), self.get(x, y).strain));
+            }
+        }
+
+        encoder.write_header()?.write_image_data(&data)?;
+
+        Ok(())
+    }
+}
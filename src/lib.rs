@@ -0,0 +1,31 @@
+//! Headless falling sand simulation.
+//!
+//! The [`World`] owns the particle grid and steps it forward one tick at a time without
+//! needing a window, so it can be driven from the renderer, tests or batch tools alike.
+
+mod brush;
+mod chunk;
+mod hash;
+mod history;
+mod image;
+mod material;
+mod particle;
+pub mod replay;
+#[cfg(feature = "scripting")]
+mod script;
+pub mod snapshot;
+mod world;
+
+pub use brush::{Brush, BrushShape, Drawing, Tool, ToolInput};
+pub use chunk::ChunkState;
+pub use history::History;
+pub use image::{PngError, PngImport, UnmappedPixel};
+pub use material::{
+    Colour, Emission, Material, MaterialError, Materials, Movement, Reaction, Transition,
+};
+pub use particle::{Particle, Strain, AMBIENT_TEMPERATURE};
+pub use replay::{Recorder, Replay, ReplayError};
+#[cfg(feature = "scripting")]
+pub use script::{Script, ScriptError};
+pub use snapshot::SnapshotError;
+pub use world::World;
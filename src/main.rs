@@ -1,820 +1,363 @@
-use std::collections::HashSet;
-
-use coffee::graphics::{
-    Batch, Color, Font, Frame, Image, Point, Rectangle, Sprite, Text, Window, WindowSettings,
-};
-use coffee::input::{keyboard, mouse, Input};
-use coffee::load::{loading_screen::ProgressBar, Join, Task};
-use coffee::{input, Game, Result, Timer};
-use nalgebra::Vector2;
-use rand::*;
-use rayon::prelude::*;
-
-fn main() -> Result<()> {
-    FallingSand::run(WindowSettings {
-        title: String::from("Falling Sand - brewed with Coffee!"),
-        size: (512, 512),
-        resizable: false,
-        fullscreen: false,
-        maximized: false,
-    })
-}
-
-struct Inputs {
-    cursor_position: Point,
-    mouse_wheel: Point,
-    keys_pressed: HashSet<keyboard::KeyCode>,
-    mouse_buttons_pressed: HashSet<mouse::Button>,
-    text_buffer: String,
+use std::error::Error;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
+
+use clap::{value_t_or_exit, App, AppSettings, Arg, ArgMatches, SubCommand};
+#[cfg(feature = "gui")]
+use falling_sand_rs::snapshot::MAX_CELLS;
+#[cfg(feature = "scripting")]
+use falling_sand_rs::Script;
+use falling_sand_rs::{Materials, Replay, World};
+
+mod console;
+#[cfg(feature = "gui")]
+mod gui;
+
+const MATERIALS_PATH: &str = "materials.toml";
+
+/// Options for the windowed simulation.
+#[cfg(feature = "gui")]
+pub struct Settings {
+    grid_width: usize,
+    grid_height: usize,
+    // multiplies the size of particles
+    scale: f32,
+    materials: Arc<Materials>,
+    // bytes of undo history to keep
+    undo_budget: usize,
+    // where to record the session to
+    record: Option<String>,
+    #[cfg(feature = "scripting")]
+    script: Option<String>,
 }
 
-impl Input for Inputs {
-    fn new() -> Inputs {
-        Inputs {
-            cursor_position: Point::new(0.0, 0.0),
-            mouse_wheel: Point::new(0.0, 0.0),
-            keys_pressed: HashSet::new(),
-            mouse_buttons_pressed: HashSet::new(),
-            text_buffer: String::new(),
-        }
-    }
-
-    fn update(&mut self, event: input::Event) {
-        match event {
-            input::Event::Mouse(mouse_event) => match mouse_event {
-                mouse::Event::CursorMoved { x, y } => {
-                    self.cursor_position = Point::new(x, y);
-                }
-                mouse::Event::Input { state, button } => match state {
-                    input::ButtonState::Pressed => {
-                        self.mouse_buttons_pressed.insert(button);
-                    }
-                    input::ButtonState::Released => {
-                        self.mouse_buttons_pressed.remove(&button);
-                    }
-                },
-                mouse::Event::WheelScrolled { delta_x, delta_y } => {
-                    self.mouse_wheel = Point::new(delta_x, delta_y);
-                }
-                _ => {}
-            },
-            input::Event::Keyboard(keyboard_event) => match keyboard_event {
-                keyboard::Event::TextEntered { character } => {
-                    self.text_buffer.push(character);
-                }
-                keyboard::Event::Input { key_code, state } => match state {
-                    input::ButtonState::Pressed => {
-                        self.keys_pressed.insert(key_code);
-                    }
-                    input::ButtonState::Released => {
-                        self.keys_pressed.remove(&key_code);
-                    }
-                },
-            },
-            _ => {}
-        }
-    }
-
-    fn clear(&mut self) {
-        self.text_buffer.clear();
+fn main() {
+    let materials_arg = || {
+        Arg::with_name("materials")
+            .long("materials")
+            .value_name("FILE")
+            .help("Material definitions to use instead of the built-in set")
+    };
+    let script_arg = || {
+        Arg::with_name("script")
+            .long("script")
+            .value_name("FILE")
+            .help("Rhai script to set the world up and run after every tick")
+    };
+
+    let matches = App::new("Falling Sand")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Opens a window to paint and watch the simulation")
+                .arg(
+                    Arg::with_name("width")
+                        .long("width")
+                        .value_name("CELLS")
+                        .default_value("128")
+                        .help("Grid width in cells"),
+                )
+                .arg(
+                    Arg::with_name("height")
+                        .long("height")
+                        .value_name("CELLS")
+                        .default_value("128")
+                        .help("Grid height in cells"),
+                )
+                .arg(
+                    Arg::with_name("scale")
+                        .long("scale")
+                        .value_name("PIXELS")
+                        .default_value("4")
+                        .help("Size of each cell on screen"),
+                )
+                .arg(
+                    Arg::with_name("undo-memory")
+                        .long("undo-memory")
+                        .value_name("MB")
+                        .default_value("64")
+                        .help("Memory to keep undo history in"),
+                )
+                .arg(
+                    Arg::with_name("record")
+                        .long("record")
+                        .value_name("FILE")
+                        .help("Records the session to a replay file"),
+                )
+                .arg(materials_arg())
+                .arg(script_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("simulate")
+                .about("Steps a saved world without opening a window")
+                .arg(
+                    Arg::with_name("input")
+                        .long("input")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("World to start from, a snapshot or .png"),
+                )
+                .arg(
+                    Arg::with_name("ticks")
+                        .long("ticks")
+                        .value_name("N")
+                        .required(true)
+                        .help("Number of ticks to step"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("Where to write the result, a snapshot or .png"),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .value_name("SEED")
+                        .default_value("0")
+                        .help("Seed for the simulation's random decisions"),
+                )
+                .arg(materials_arg())
+                .arg(script_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("Plays a recorded session back without opening a window")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("A replay recorded with `run --record`"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Where to write the final world, a snapshot or .png"),
+                )
+                .arg(materials_arg())
+                .arg(script_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Prints a saved world's dimensions and particle counts")
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("A snapshot or .png"),
+                )
+                .arg(materials_arg()),
+        )
+        .get_matches();
+
+    let result = match matches.subcommand() {
+        ("run", Some(m)) => run(m),
+        ("simulate", Some(m)) => simulate(m),
+        ("replay", Some(m)) => replay(m),
+        ("info", Some(m)) => info(m),
+        _ => unreachable!(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {}", e);
+        process::exit(1);
     }
 }
 
-#[repr(C)]
-#[derive(Clone, Copy)]
-pub struct Particle {
-    strain: Strain,
-    update: bool,
-    lifetime: i16,
+// Falls back to materials.toml in the working directory if there is one
+fn materials(m: &ArgMatches) -> Result<Arc<Materials>, Box<dyn Error>> {
+    let path = match m.value_of("materials") {
+        Some(path) => path,
+        None if Path::new(MATERIALS_PATH).exists() => MATERIALS_PATH,
+        None => return Ok(Materials::builtin()),
+    };
+
+    Materials::load(path)
+        .map(Arc::new)
+        .map_err(|e| format!("{}: {}", path, e).into())
 }
 
-impl Default for Particle {
-    fn default() -> Self {
-        Particle {
-            strain: Strain::Empty,
-            update: false,
-            lifetime: -1,
-        }
+// The script named on the command line, after running its top level against `world`
+#[cfg(feature = "scripting")]
+fn load_script(m: &ArgMatches, world: &mut World) -> Result<Option<Script>, Box<dyn Error>> {
+    match m.value_of("script") {
+        Some(path) => Script::load(path, world)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", path, e).into()),
+        None => Ok(None),
     }
 }
 
-#[repr(u8)]
-#[derive(Clone, Copy, Eq, PartialEq)]
-pub enum Strain {
-    Empty = 0,
-    Sand = 1,
-    Water = 2,
-    Wood = 3,
-    Fire = 4,
-    Glass = 5,
-    GlassMolten = 6,
-    OilCrude = 7,
-    Ash = 8,
-    AshBurning = 9,
-    WoodHot = 10,
-}
-
-impl Strain {
-    fn to_colour_id(&self) -> u16 {
-        match self {
-            Strain::Sand => 1,
-            Strain::Water => 2,
-            Strain::Wood => 3,
-            Strain::Fire => 4,
-            Strain::Glass => 5,
-            Strain::GlassMolten => 6,
-            Strain::OilCrude => 7,
-            Strain::Ash => 8,
-            Strain::AshBurning => 9,
-            Strain::WoodHot => 10,
-            _ => 0,
-        }
-    }
-
-    fn density(&self) -> u16 {
-        match self {
-            Strain::Sand => 1600,
-            Strain::Water => 1000,
-            Strain::Wood => 9999,
-            Strain::Fire => 600,
-            Strain::Glass => 9999,
-            Strain::GlassMolten => 1600,
-            Strain::OilCrude => 930,
-            Strain::Ash => 1600,
-            Strain::AshBurning => 1600,
-            Strain::WoodHot => 9999,
-            _ => 1000,
-        }
-    }
-
-    fn to_str(&self) -> &'static str {
-        match self {
-            Strain::Empty => "Empty",
-            Strain::Sand => "Sand",
-            Strain::Water => "Water",
-            Strain::Wood => "Wood",
-            Strain::Fire => "Fire",
-            Strain::Glass => "Glass",
-            Strain::GlassMolten => "Molten Glass",
-            Strain::OilCrude => "Crude Oil",
-            Strain::Ash => "Ash",
-            Strain::AshBurning => "Embers",
-            Strain::WoodHot => "Burning Wood",
-            _ => "",
-        }
-    }
-
-    // how long it survives in ticks
-    fn base_lifetime(&self) -> i16 {
-        let mut rng = rand::thread_rng();
-
-        match self {
-            Strain::Fire => rng.gen_range(60, 100),
-            Strain::GlassMolten => rng.gen_range(240, 480),
-            Strain::AshBurning => rng.gen_range(120, 360),
-            Strain::WoodHot => rng.gen_range(120, 240),
-            _ => -1,
-        }
-    }
-
-    // what it turns into when it dies
-    fn death_strain(&self) -> Strain {
-        match self {
-            Strain::GlassMolten => Strain::Glass,
-            Strain::Wood => Strain::WoodHot,
-            Strain::WoodHot => Strain::AshBurning,
-            Strain::AshBurning => Strain::Ash,
-            _ => Strain::Empty,
-        }
-    }
-
-    // out of 100
-    fn ignite_chance(&self) -> u8 {
-        match self {
-            Strain::Wood => 5,
-            Strain::OilCrude => 2,
-            _ => 0,
-        }
-    }
-
-    // can it ignite particles around it?
-    fn can_ignite_others(&self) -> bool {
-        match self {
-            Strain::Fire => true,
-            Strain::AshBurning => true,
-            Strain::WoodHot => true,
-            _ => false,
-        }
-    }
-
-    fn reactable_strains(&self) -> Vec<(Strain, i8, Strain)> {
-        match self {
-            Strain::Sand => vec![(Strain::Fire, 1, Strain::GlassMolten)],
-            Strain::GlassMolten => vec![(Strain::Water, 50, Strain::Glass)],
-            Strain::Glass => vec![(Strain::Fire, 10, Strain::GlassMolten)],
-
-            _ => vec![],
-        }
-    }
-
-    // what strain (if any) does this strain generate above it, and what is that probability out of 100? ei. burning wood can make fire
-    fn emits_above(&self) -> (Strain, u8) {
-        match self {
-            Strain::WoodHot => (Strain::Fire, 2),
-
-            _ => (Strain::Empty, 0),
-        }
+#[cfg(not(feature = "scripting"))]
+fn check_no_script(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    if m.is_present("script") {
+        return Err(
+            "this build has no script support, rebuild with the `scripting` feature".into(),
+        );
     }
+    Ok(())
 }
 
-struct FallingSand {
-    font: Font,
-    grid: Vec<Particle>,
-    grid_width: usize,
-    grid_height: usize,
-    batch: Batch,
-    update: bool,
-    cursor_position: Point,
-    mouse_wheel: Point,
-    keys_pressed: HashSet<keyboard::KeyCode>,
-    mouse_buttons_pressed: HashSet<mouse::Button>,
-    text_buffer: String,
-    particles_updated: u64,
-    active_strain: Strain,
-    four_adj_particles: [Vector2<isize>; 4],
+fn is_png(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
 }
 
-impl FallingSand {
-    const MAX_TEXTSIZE: usize = 40;
-
-    fn new(batch: Batch, font: Font, x: usize, y: usize) -> FallingSand {
-        FallingSand {
-            font,
-            grid: vec![Particle::default(); x * y],
-            grid_width: x,
-            grid_height: y,
-            batch,
-            update: false,
-            cursor_position: Point::new(0.0, 0.0),
-            mouse_wheel: Point::new(0.0, 0.0),
-            keys_pressed: HashSet::new(),
-            mouse_buttons_pressed: HashSet::new(),
-            text_buffer: String::with_capacity(Self::MAX_TEXTSIZE),
-            particles_updated: 0,
-            active_strain: Strain::Sand,
-            four_adj_particles: [
-                Vector2::new(-1, 0),
-                Vector2::new(1, 0),
-                Vector2::new(0, -1),
-                Vector2::new(0, 1),
-            ],
-        }
-    }
-
-    fn load() -> Task<FallingSand> {
-        (
-            Task::using_gpu(|gpu| Image::from_colors(gpu, &COLORS)),
-            Font::load_from_bytes(include_bytes!("../resources/Gamepixies-8MO6n.ttf")),
-        )
-            .join()
-            .map(|(palette, font)| FallingSand::new(Batch::new(palette), font, 128, 128))
-    }
-
-    fn set_row(&mut self, strain: Strain, row: usize) {
-        for x in 0..128 {
-            self.set_strain(x, row, strain);
+// Loads a snapshot or png into a world seeded with `seed`
+fn load_world(path: &str, seed: u64, materials: Arc<Materials>) -> Result<World, Box<dyn Error>> {
+    if is_png(path) {
+        let import = World::from_png_with_materials(path, seed, materials)?;
+        for p in import.unmapped.iter() {
+            eprintln!(
+                "{}: pixel ({}, {}) {:?} has no exact material, using {}",
+                path,
+                p.x,
+                p.y,
+                p.rgb,
+                import.world.materials()[p.strain].id
+            );
         }
+        Ok(import.world)
+    } else {
+        let mut world = World::load_with_materials(path, materials)?;
+        world.reseed(seed);
+        Ok(world)
     }
+}
 
-    fn index(&mut self, x: usize, y: usize) -> usize {
-        x + self.grid_width * y
-    }
-
-    fn get(&mut self, x: usize, y: usize) -> Particle {
-        let i: usize = self.index(x, y);
-
-        self.grid[i]
-    }
-
-    fn set(&mut self, x: usize, y: usize, p: Particle) {
-        let i: usize = self.index(x, y);
-
-        self.grid[i] = p;
+fn save_world(world: &World, path: &str) -> Result<(), Box<dyn Error>> {
+    if is_png(path) {
+        world.to_png(path)?;
+    } else {
+        world.save(path)?;
     }
+    Ok(())
+}
 
-    fn set_strain(&mut self, x: usize, y: usize, s: Strain) {
-        let i: usize = self.index(x, y);
+#[cfg(feature = "gui")]
+fn run(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let grid_width = value_t_or_exit!(m, "width", usize);
+    let grid_height = value_t_or_exit!(m, "height", usize);
+    let scale = value_t_or_exit!(m, "scale", f32);
+    let undo_memory = value_t_or_exit!(m, "undo-memory", usize);
 
-        self.grid[i].strain = s;
+    if grid_width == 0 || grid_height == 0 || !scale.is_finite() || scale <= 0. {
+        return Err("width, height and scale must be greater than zero".into());
     }
-
-    fn is_particle_empty(&mut self, x: usize, y: usize) -> bool {
-        self.get(x, y).strain == Strain::Empty
+    if grid_width.saturating_mul(grid_height) > MAX_CELLS {
+        return Err(format!("grids are limited to {} cells", MAX_CELLS).into());
     }
 
-    fn swap(&mut self, x1: usize, y1: usize, x2: usize, y2: usize) {
-        let cur: &Particle = &self.get(x1, y1);
-        let other: &Particle = &self.get(x2, y2);
-
-        self.set(x1, y1, *other);
-        self.set(x2, y2, *cur);
-    }
-
-    fn apply_gravity(&mut self, x: usize, y: usize, val: isize) -> bool {
-        if ((y as isize + val) <= 0) || (y as isize + val > self.grid_height as isize - 1) {
-            return false;
-        } else {
-            // Compare densities
-            let dx = self.get(x, y).strain.density();
-            let dy = self.get(x, (y as isize + val) as usize).strain.density();
+    #[cfg(not(feature = "scripting"))]
+    check_no_script(m)?;
+
+    gui::run(Settings {
+        grid_width,
+        grid_height,
+        scale,
+        materials: materials(m)?,
+        undo_budget: undo_memory.saturating_mul(1024 * 1024),
+        record: m.value_of("record").map(String::from),
+        #[cfg(feature = "scripting")]
+        script: m.value_of("script").map(String::from),
+    })?;
+
+    Ok(())
+}
 
-            if dx > dy || self.is_particle_empty(x, (y as isize + val) as usize) {
-                self.swap(x, y, x, (y as isize + val) as usize);
+#[cfg(not(feature = "gui"))]
+fn run(_m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    Err("this build has no window support, rebuild with the `gui` feature".into())
+}
 
-                return true;
-            }
-        }
+fn simulate(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let ticks = value_t_or_exit!(m, "ticks", u64);
+    let seed = value_t_or_exit!(m, "seed", u64);
+    let input = m.value_of("input").unwrap();
+    let output = m.value_of("output").unwrap();
 
-        return false;
-    }
+    let mut world = load_world(input, seed, materials(m)?)?;
 
-    fn apply_tumble(&mut self, x: usize, y: usize) -> bool {
-        let mut translate: Vector2<isize> = nalgebra::Vector2::new(0, 0);
-
-        let &grid_height: &usize = &self.grid_height;
-        let &grid_width: &usize = &self.grid_width;
-
-        if y + 1 < grid_height && x > 0 && self.is_particle_empty(x - 1, y + 1) {
-            // Move down left
-            translate.x = -1;
-            translate.y = 1;
-        } else if y + 1 < grid_height && x + 1 < grid_width && self.is_particle_empty(x + 1, y + 1)
-        {
-            // Move down right
-            translate.x = 1;
-            translate.y = 1;
-        }
+    #[cfg(feature = "scripting")]
+    let mut script = load_script(m, &mut world)?;
+    #[cfg(not(feature = "scripting"))]
+    check_no_script(m)?;
 
-        if translate.x != 0 || translate.y != 0 {
-            self.swap(
-                x,
-                y,
-                (x as isize + translate.x) as usize,
-                (y as isize + translate.y) as usize,
-            );
+    for _ in 0..ticks {
+        world.step();
 
-            return true;
+        #[cfg(feature = "scripting")]
+        if let Some(script) = script.as_mut() {
+            script.tick(&mut world)?;
         }
-
-        false
     }
 
-    fn apply_spread(&mut self, x: usize, y: usize) -> bool {
-        let mut trans_x: isize = 0;
-
-        let &grid_width: &usize = &self.grid_width;
-
-        let dir: isize;
-        if random() {
-            dir = -1;
-        } else {
-            dir = 1;
-        }
-
-        if x > 0
-            && (x as isize + dir < grid_width as isize)
-            && self.is_particle_empty((x as isize + dir) as usize, y)
-        {
-            trans_x = dir;
-        }
-
-        if trans_x != 0 {
-            self.swap(x, y, (x as isize + trans_x) as usize, y);
+    save_world(&world, output)
+}
 
-            return true;
+// Plays a replay back with the same materials and script it was recorded with, checking the
+// world against it as it goes
+fn replay(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let path = m.value_of("file").unwrap();
+    let materials = materials(m)?;
+    let replay = Replay::load(path, &materials).map_err(|e| format!("{}: {}", path, e))?;
+    let mut world = replay.world(materials);
+
+    #[cfg(feature = "scripting")]
+    let mut script = load_script(m, &mut world)?;
+    #[cfg(not(feature = "scripting"))]
+    check_no_script(m)?;
+
+    let mut playback = replay.playback();
+    let console = |world: &mut World, line: &str| {
+        // Saving doesn't change the world, so there's no need to overwrite the files again. A
+        // load is recorded with the snapshot it loaded, and only as a line if its file couldn't
+        // be read, so whatever is in that file now is no part of the session.
+        if line.split_whitespace().next() != Some("save") && console::load_path(line).is_none() {
+            let _ = console::run(world, line);
         }
+    };
 
-        false
-    }
-
-    fn apply_density(&mut self, x: usize, y: usize) -> bool {
-        if y + 1 < self.grid_height {
-            let cur = self.get(x, y);
-            let bel = self.get(x, y + 1);
-
-            // If the current particle's density is greater than the particle below's
-            if cur.strain.density() > bel.strain.density() {
-                self.swap(x, y, x, y + 1);
+    while let Some(steps) = playback.edit(&mut world, console) {
+        for _ in 0..steps {
+            world.step();
 
-                return true;
+            // like the window, carry on without a script once it fails
+            #[cfg(feature = "scripting")]
+            if let Some(e) = script.as_mut().and_then(|s| s.tick(&mut world).err()) {
+                eprintln!("script stopped: {}", e);
+                script = None;
             }
         }
 
-        false
-    }
-
-    fn spawn_particle(&mut self, x: usize, y: usize, p: Particle) {
-        if x < self.grid_width && y < self.grid_height {
-            self.set(x, y, p);
-        }
+        playback
+            .verify(&world)
+            .map_err(|e| format!("{}: {}", path, e))?;
     }
-}
-
-impl Game for FallingSand {
-    type Input = Inputs;
-    type LoadingScreen = ProgressBar;
-
-    const TICKS_PER_SECOND: u16 = 60;
-
-    fn load(_window: &Window) -> Task<Self> {
-        Task::stage("Loading...", FallingSand::load())
-    }
-
-    fn draw(&mut self, frame: &mut Frame, _timer: &Timer) {
-        frame.clear(Color::BLACK);
-
-        let target = &mut frame.as_target();
-
-        // multiplies the size of particles
-        let scale: f32 = 4.;
-
-        self.batch.clear();
-
-        // collects all particles into a batch of rectangle sprites
-        for x in 0..self.grid_width {
-            for y in 0..self.grid_height {
-                let p = self.get(x as usize, y as usize);
-                if p.strain != Strain::Empty {
-                    self.batch.add(Sprite {
-                        source: Rectangle {
-                            x: p.strain.to_colour_id(),
-                            y: 0,
-                            width: 1,
-                            height: 1,
-                        },
-                        position: Point::new(x as f32 * scale, y as f32 * scale),
-                        scale: (1.0 * scale, 1.0 * scale),
-                    });
-                }
-            }
-        }
 
-        // draw particle batch
-        self.batch.draw(target);
-
-        // add and then draw text
-        self.font.add(Text {
-            content: &*format!("particles_updated={}", self.particles_updated),
-            position: Point::new(8.0, 2.0),
-            size: 16.0,
-            color: COLORS[0],
-            ..Text::default()
-        });
-
-        let cur = self.cursor_position;
-        let cur_x = (cur.x / 4.) as usize;
-        let cur_y = (cur.y / 4.) as usize;
-
-        let under_cur = if cur_x < self.grid_width && cur_y < self.grid_height {
-            self.get((cur.x / 4.) as usize, (cur.y / 4.) as usize)
-                .strain
-                .to_str()
-        } else {
-            "Empty"
-        };
-
-        self.font.add(Text {
-            content: &*format!("under cursor: {}", under_cur),
-            position: Point::new(8., 16.),
-            size: 16.0,
-            color: COLORS[0],
-            ..Text::default()
-        });
-
-        self.font.add(Text {
-            content: &*format!("active: {}", self.active_strain.to_str()),
-            position: Point::new(8., 30.),
-            size: 16.0,
-            color: COLORS[self.active_strain.to_colour_id() as usize],
-            ..Text::default()
-        });
-
-        self.font.draw(target);
-    }
+    println!("{}: replayed {} ticks", path, playback.ticks());
 
-    //noinspection RsBorrowChecker
-    fn interact(&mut self, input: &mut Inputs, _window: &mut Window) {
-        self.cursor_position = input.cursor_position;
-        self.mouse_wheel = input.mouse_wheel;
-        self.keys_pressed = input.keys_pressed.clone();
-        self.mouse_buttons_pressed = input.mouse_buttons_pressed.clone();
-
-        if !input.text_buffer.is_empty() {
-            for c in input.text_buffer.chars() {
-                match c {
-                    // Match ASCII backspace and delete from the text buffer
-                    '\u{0008}' => {
-                        self.text_buffer.pop();
-                    }
-                    _ => {
-                        if self.text_buffer.chars().count() < Self::MAX_TEXTSIZE {
-                            self.text_buffer.push_str(&input.text_buffer);
-                        }
-                    }
-                }
-            }
-        }
+    match m.value_of("output") {
+        Some(output) => save_world(&world, output),
+        None => Ok(()),
     }
+}
 
-    fn update(&mut self, _window: &Window) {
-        // Update current strain for mouse click
-        let x: Option<&keyboard::KeyCode> = self.keys_pressed.par_iter().find_first(|&&x| {
-            x == keyboard::KeyCode::E
-                || x == keyboard::KeyCode::Key1
-                || x == keyboard::KeyCode::Key2
-                || x == keyboard::KeyCode::Key3
-                || x == keyboard::KeyCode::Key4
-                || x == keyboard::KeyCode::Key5
-        });
-
-        if x != None {
-            self.active_strain = match x.unwrap() {
-                keyboard::KeyCode::E => Strain::Empty,
-                keyboard::KeyCode::Key1 => Strain::Sand,
-                keyboard::KeyCode::Key2 => Strain::Water,
-                keyboard::KeyCode::Key3 => Strain::Wood,
-                keyboard::KeyCode::Key4 => Strain::Fire,
-                keyboard::KeyCode::Key5 => Strain::OilCrude,
-                _ => Strain::Sand,
-            }
-        }
+fn info(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    // never stepped, so any seed will do
+    let world = load_world(m.value_of("file").unwrap(), 0, materials(m)?)?;
 
-        // Spawn particle at mouse
-        let left_down = self.mouse_buttons_pressed.contains(&mouse::Button::Left);
-        let right_down = self.mouse_buttons_pressed.contains(&mouse::Button::Right);
-
-        if left_down {
-            let x: usize = (self.cursor_position.x / 4.) as usize;
-            let y: usize = (self.cursor_position.y / 4.) as usize;
-
-            let points: Vec<Vector2<isize>> = vec![
-                Vector2::new(0, 0),
-                Vector2::new(-1, 0),
-                Vector2::new(1, 0),
-                Vector2::new(0, -1),
-                Vector2::new(0, 1),
-            ];
-
-            for v in points {
-                let xp = (x as isize + v.x) as usize;
-                let yp = (y as isize + v.y) as usize;
-
-                if xp < self.grid_width
-                    && yp < self.grid_height
-                    && (self.is_particle_empty(xp, yp) || self.active_strain == Strain::Empty)
-                {
-                    self.spawn_particle(
-                        xp,
-                        yp,
-                        Particle {
-                            strain: self.active_strain,
-                            lifetime: self.active_strain.base_lifetime(),
-                            ..Default::default()
-                        },
-                    );
-                }
-            }
-        }
+    println!("size: {}x{}", world.width(), world.height());
 
-        // Reset updated particles stat
-        self.particles_updated = 0;
-
-        // Update particle grid - bottom to top; left to right
-        for y in (0..self.grid_height).rev() {
-            for x in 0..self.grid_width {
-                let mut p = self.get(x, y);
-
-                // check if dead
-                if p.lifetime == 0 {
-                    p.strain = p.strain.death_strain();
-                    p.lifetime = p.strain.base_lifetime();
-
-                    // save
-                    self.set(x, y, p);
-                }
-                // check the particle has not been updated this frame & ensure it isn't empty
-                else if p.update == self.update && p.strain != Strain::Empty {
-                    p.update = !p.update;
-
-                    // decrease lifetime if needed
-                    if p.lifetime > 0 {
-                        p.lifetime -= 1;
-                    }
-
-                    // Attempt reaction
-                    for r in p.strain.reactable_strains().iter() {
-                        let itr = self.four_adj_particles;
-                        for v in itr.iter() {
-                            if ((x as isize + v.x) as usize) < self.grid_width
-                                && ((y as isize + v.y) as usize) < self.grid_height
-                            {
-                                let other = self
-                                    .get((x as isize + v.x) as usize, (y as isize + v.y) as usize);
-
-                                if other.strain == r.0 && thread_rng().gen_range(0, 100) <= r.1 {
-                                    // Reaction successful
-                                    p.strain = r.2;
-                                    p.lifetime = p.strain.base_lifetime();
-                                    break;
-                                }
-                            }
-                        }
-                    }
-
-                    // save state to grid
-                    self.set(x, y, p);
-
-                    let mut rng = rand::thread_rng();
-
-                    // Attempt to ignite nearby particles
-                    if p.strain.can_ignite_others() {
-                        let itr = self.four_adj_particles;
-                        for v in itr.iter() {
-                            if ((x as isize + v.x) as usize) < self.grid_width
-                                && ((y as isize + v.y) as usize) < self.grid_height
-                            {
-                                let mut p = self
-                                    .get((x as isize + v.x) as usize, (y as isize + v.y) as usize);
-
-                                if p.strain != Strain::Empty && p.strain.ignite_chance() > 0 {
-                                    if rng.gen_range(0, 100) <= p.strain.ignite_chance() {
-                                        p.strain = if p.strain.death_strain() == Strain::Empty {
-                                            Strain::Fire
-                                        } else {
-                                            p.strain.death_strain()
-                                        };
-                                        p.lifetime = p.strain.base_lifetime();
-
-                                        self.set(
-                                            (x as isize + v.x) as usize,
-                                            (y as isize + v.y) as usize,
-                                            p,
-                                        );
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                    }
-
-                    let emit_above = p.strain.emits_above();
-
-                    // Attempt to emit from above
-                    if emit_above.0 != Strain::Empty
-                        && rng.gen_range(0, 100) <= emit_above.1
-                        && ((y as isize) - 1 > 0 && self.is_particle_empty(x, y - 1))
-                    {
-                        let above = Particle {
-                            strain: emit_above.0,
-                            update: p.update,
-                            lifetime: emit_above.0.base_lifetime(),
-                        };
-                        self.set(x, y - 1, above);
-                    }
-
-                    // select particle update behaviour depending on its Strain
-                    match p.strain {
-                        Strain::Sand | Strain::Ash | Strain::AshBurning => {
-                            if !self.apply_gravity(x, y, 1) {
-                                self.apply_tumble(x, y);
-                            }
-                        }
-                        Strain::Water => {
-                            if !self.apply_gravity(x, y, 1) {
-                                if !self.apply_tumble(x, y) {
-                                    self.apply_spread(x, y);
-                                }
-                            }
-                        }
-                        Strain::Fire => {
-                            if random() {
-                                self.apply_gravity(x, y, -1);
-                            }
-                            if random() {
-                                self.apply_spread(x, y);
-                            }
-                        }
-                        Strain::GlassMolten => {
-                            if !self.apply_gravity(x, y, 1) {
-                                // Randomly dont move to appear thicker
-                                if random() {
-                                    if !self.apply_tumble(x, y) {
-                                        self.apply_spread(x, y);
-                                    }
-                                }
-                            }
-                        }
-                        Strain::OilCrude => {
-                            if !self.apply_gravity(x, y, 1) {
-                                // Randomly dont move to appear thicker
-                                if rng.gen_range(0, 100) <= 25 {
-                                    if !self.apply_tumble(x, y) {
-                                        self.apply_spread(x, y);
-                                    }
-                                }
-                            }
-                        }
-
-                        _ => {}
-                    }
-
-                    self.particles_updated += 1;
-                }
-            }
+    for (strain, count) in world.materials().strains().zip(world.strain_counts()) {
+        if count > 0 {
+            println!("{}: {}", world.materials()[strain].id, count);
         }
-
-        self.update = !self.update;
     }
 
-    const DEBUG_KEY: Option<keyboard::KeyCode> = Some(keyboard::KeyCode::F12);
+    Ok(())
 }
-
-const COLORS: [Color; 11] = [
-    // White
-    Color {
-        r: 1.0,
-        g: 1.0,
-        b: 1.0,
-        a: 1.0,
-    },
-    // Sand
-    Color {
-        r: 1.0,
-        g: 0.87,
-        b: 0.67,
-        a: 1.0,
-    },
-    // Water
-    Color {
-        r: 0.117,
-        g: 0.564,
-        b: 1.0,
-        a: 1.0,
-    },
-    // Wood
-    Color {
-        r: 0.6274,
-        g: 0.3215,
-        b: 0.1647,
-        a: 1.0,
-    },
-    // Fire
-    Color {
-        r: 1.0,
-        g: 0.2705,
-        b: 0.0,
-        a: 1.0,
-    },
-    // Glass
-    Color {
-        r: 0.85,
-        g: 0.85,
-        b: 0.85,
-        a: 1.0,
-    },
-    // Molten glass
-    Color {
-        r: 1.0,
-        g: 0.498,
-        b: 0.3137,
-        a: 1.0,
-    },
-    // Oil
-    Color {
-        r: 0.0666,
-        g: 0.0627,
-        b: 0.047,
-        a: 1.0,
-    },
-    // Ash
-    Color {
-        r: 0.819,
-        g: 0.819,
-        b: 0.819,
-        a: 1.0,
-    },
-    // AshBurning
-    Color {
-        r: 0.964,
-        g: 0.435,
-        b: 0.247,
-        a: 1.0,
-    },
-    // WoodBurning
-    Color {
-        r: 0.819,
-        g: 0.309,
-        b: 0.0,
-        a: 1.0,
-    },
-];
@@ -0,0 +1,656 @@
+//! The material registry: every strain's properties, loaded from a TOML file.
+//!
+//! See `resources/materials.toml` for the format and the built-in materials.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::ops::Index;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::hash::Fnv1a;
+use crate::particle::{Strain, AMBIENT_TEMPERATURE};
+
+const BUILTIN: &str = include_str!("../resources/materials.toml");
+
+/// The furthest a liquid can be set to flow sideways in one tick.
+pub(crate) const MAX_DISPERSION: u8 = 8;
+
+/// An RGBA colour with each channel in the range `0.0..=1.0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Colour {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Colour {
+    pub fn to_rgba8(self) -> [u8; 4] {
+        let channel = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        [
+            channel(self.r),
+            channel(self.g),
+            channel(self.b),
+            channel(self.a),
+        ]
+    }
+}
+
+/// How a material moves each tick.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Movement {
+    /// Never moves, and nothing can push it out of the way.
+    #[default]
+    Static,
+    /// Falls and tumbles down diagonally.
+    Powder,
+    /// Falls, tumbles and spreads sideways.
+    Liquid,
+    /// Flickers upwards and sideways at random.
+    Flame,
+    /// Rises straight up or diagonally, drifting sideways under ceilings and settling above
+    /// heavier gases.
+    Gas,
+}
+
+/// Turns a material `into` another, `chance` out of 100 per tick, when it is next to `with`.
+#[derive(Clone, Copy, Debug)]
+pub struct Reaction {
+    pub with: Strain,
+    pub chance: u8,
+    pub into: Strain,
+}
+
+/// Turns a material `into` another once its temperature crosses `at`.
+#[derive(Clone, Copy, Debug)]
+pub struct Transition {
+    pub at: f32,
+    pub into: Strain,
+}
+
+/// Spawns `strain` in the empty cell above, `chance` out of 100 per tick.
+#[derive(Clone, Copy, Debug)]
+pub struct Emission {
+    pub strain: Strain,
+    pub chance: u8,
+}
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub id: String,
+    pub name: String,
+    pub colour: Colour,
+    // only meaningful for materials that move, static ones are never displaced
+    pub density: u16,
+    pub movement: Movement,
+    // out of 100
+    pub flow_chance: u8,
+    // how many cells sideways a liquid looks for somewhere to flow each tick
+    pub dispersion: u8,
+    // out of 100, how much of its falling speed is thrown sideways when it lands
+    pub splash: u8,
+    // how long it survives in ticks, if it dies at all
+    pub lifetime: Option<(i16, i16)>,
+    // what it turns into when it dies
+    pub dies_into: Strain,
+    // temperature it spawns at
+    pub temperature: f32,
+    // out of 100, how quickly heat passes between it and its neighbours
+    pub conductivity: u8,
+    // temperature it holds itself at while burning, heating everything around it
+    pub burns_at: Option<f32>,
+    // catches light `ignite_chance` out of 100 per tick once at or above the ignition point
+    pub ignition: Option<Transition>,
+    pub ignite_chance: u8,
+    pub melting: Option<Transition>,
+    pub boiling: Option<Transition>,
+    // turns into something else at or below this temperature
+    pub freezing: Option<Transition>,
+    pub reactions: Vec<Reaction>,
+    pub emits_above: Vec<Emission>,
+}
+
+impl Material {
+    /// A freshly rolled lifetime, or -1 for materials that live forever.
+    pub fn base_lifetime<R: Rng>(&self, rng: &mut R) -> i16 {
+        match self.lifetime {
+            Some((min, max)) => rng.gen_range(min, max),
+            None => -1,
+        }
+    }
+
+    /// Whether nothing can push this material out of the way. Empty space is static too, but
+    /// anything may move into it.
+    pub fn is_immovable(&self) -> bool {
+        self.movement == Movement::Static
+    }
+
+    /// The temperature a fresh particle of this material starts at.
+    pub fn base_temperature(&self) -> f32 {
+        self.burns_at.unwrap_or(self.temperature)
+    }
+
+    // every field, so a change to any of them changes the registry's fingerprint
+    fn hash(&self, hash: &mut Fnv1a) {
+        let option = |hash: &mut Fnv1a, present: bool| hash.write(&[present as u8]);
+
+        hash.write_str(&self.id);
+        hash.write_str(&self.name);
+        for c in [self.colour.r, self.colour.g, self.colour.b, self.colour.a] {
+            hash.write_f32(c);
+        }
+        hash.write(&self.density.to_le_bytes());
+        hash.write(&[match self.movement {
+            Movement::Static => 0,
+            Movement::Powder => 1,
+            Movement::Liquid => 2,
+            Movement::Flame => 3,
+            Movement::Gas => 4,
+        }]);
+        hash.write(&[self.flow_chance, self.dispersion, self.splash]);
+        option(hash, self.lifetime.is_some());
+        if let Some((min, max)) = self.lifetime {
+            hash.write(&min.to_le_bytes());
+            hash.write(&max.to_le_bytes());
+        }
+        hash.write(&[self.dies_into.id()]);
+        hash.write_f32(self.temperature);
+        hash.write(&[self.conductivity]);
+        option(hash, self.burns_at.is_some());
+        if let Some(t) = self.burns_at {
+            hash.write_f32(t);
+        }
+        hash.write(&[self.ignite_chance]);
+        for transition in [self.ignition, self.melting, self.boiling, self.freezing] {
+            option(hash, transition.is_some());
+            if let Some(t) = transition {
+                hash.write_f32(t.at);
+                hash.write(&[t.into.id()]);
+            }
+        }
+        hash.write(&(self.reactions.len() as u32).to_le_bytes());
+        for r in self.reactions.iter() {
+            hash.write(&[r.with.id(), r.chance, r.into.id()]);
+        }
+        hash.write(&(self.emits_above.len() as u32).to_le_bytes());
+        for e in self.emits_above.iter() {
+            hash.write(&[e.strain.id(), e.chance]);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MaterialError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// The first material is not `empty`.
+    MissingEmpty,
+    TooMany(usize),
+    DuplicateId(String),
+    MissingColour(String),
+    InvalidColour(String),
+    InvalidLifetime(String),
+    /// A field measured out of 100, like a chance or the conductivity, is above 100.
+    InvalidPercentage {
+        material: String,
+        field: &'static str,
+    },
+    /// A temperature or temperature point is infinite or not a number.
+    InvalidTemperature {
+        material: String,
+        field: &'static str,
+    },
+    MissingDensity(String),
+    InvalidDispersion(String),
+    /// A material has a temperature point but nothing to turn into when it is crossed.
+    MissingTransition {
+        material: String,
+        field: &'static str,
+    },
+    UnknownReference {
+        material: String,
+        field: &'static str,
+        target: String,
+    },
+}
+
+impl fmt::Display for MaterialError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaterialError::Io(e) => write!(f, "{}", e),
+            MaterialError::Parse(e) => write!(f, "{}", e),
+            MaterialError::MissingEmpty => write!(f, "the first material must be `empty`"),
+            MaterialError::TooMany(n) => {
+                write!(
+                    f,
+                    "{} materials defined, at most {} are allowed",
+                    n, MAX_MATERIALS
+                )
+            }
+            MaterialError::DuplicateId(id) => write!(f, "material `{}` is defined twice", id),
+            MaterialError::MissingColour(id) => write!(f, "material `{}` has no colour", id),
+            MaterialError::InvalidColour(id) => write!(
+                f,
+                "material `{}` colour must be [r, g, b] or [r, g, b, a] in 0.0 - 1.0",
+                id
+            ),
+            MaterialError::InvalidLifetime(id) => {
+                write!(
+                    f,
+                    "material `{}` lifetime must be [min, max) with 0 < min < max",
+                    id
+                )
+            }
+            MaterialError::InvalidPercentage { material, field } => {
+                write!(f, "material `{}` {} must be 0 - 100", material, field)
+            }
+            MaterialError::InvalidTemperature { material, field } => {
+                write!(
+                    f,
+                    "material `{}` {} must be a finite number",
+                    material, field
+                )
+            }
+            MaterialError::MissingDensity(id) => {
+                write!(f, "material `{}` moves but has no density", id)
+            }
+            MaterialError::InvalidDispersion(id) => write!(
+                f,
+                "material `{}` dispersion must be 0 - {}",
+                id, MAX_DISPERSION
+            ),
+            MaterialError::MissingTransition { material, field } => {
+                write!(
+                    f,
+                    "material `{}` has a {} but nothing to turn into",
+                    material, field
+                )
+            }
+            MaterialError::UnknownReference {
+                material,
+                field,
+                target,
+            } => write!(
+                f,
+                "material `{}` {} refers to unknown material `{}`",
+                material, field, target
+            ),
+        }
+    }
+}
+
+impl Error for MaterialError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MaterialError::Io(e) => Some(e),
+            MaterialError::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MaterialError {
+    fn from(e: io::Error) -> Self {
+        MaterialError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for MaterialError {
+    fn from(e: toml::de::Error) -> Self {
+        MaterialError::Parse(e)
+    }
+}
+
+const MAX_MATERIALS: usize = u8::MAX as usize + 1;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawFile {
+    material: Vec<RawMaterial>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMaterial {
+    id: String,
+    name: Option<String>,
+    colour: Option<Vec<f32>>,
+    density: Option<u16>,
+    #[serde(default)]
+    movement: Movement,
+    #[serde(default = "default_flow_chance")]
+    flow_chance: u8,
+    #[serde(default = "default_dispersion")]
+    dispersion: u8,
+    #[serde(default)]
+    splash: u8,
+    lifetime: Option<(i16, i16)>,
+    dies_into: Option<String>,
+    #[serde(default = "default_temperature")]
+    temperature: f32,
+    #[serde(default = "default_conductivity")]
+    conductivity: u8,
+    burns_at: Option<f32>,
+    ignition_point: Option<f32>,
+    #[serde(default = "default_ignite_chance")]
+    ignite_chance: u8,
+    ignites_into: Option<String>,
+    melting_point: Option<f32>,
+    melts_into: Option<String>,
+    boiling_point: Option<f32>,
+    boils_into: Option<String>,
+    freezing_point: Option<f32>,
+    freezes_into: Option<String>,
+    #[serde(default)]
+    reactions: Vec<RawReaction>,
+    #[serde(default)]
+    emits_above: OneOrMany<RawEmission>,
+}
+
+fn default_flow_chance() -> u8 {
+    100
+}
+
+fn default_dispersion() -> u8 {
+    1
+}
+
+fn default_temperature() -> f32 {
+    AMBIENT_TEMPERATURE
+}
+
+fn default_conductivity() -> u8 {
+    10
+}
+
+fn default_ignite_chance() -> u8 {
+    100
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawReaction {
+    with: String,
+    chance: u8,
+    into: String,
+}
+
+// a single table or an array of them
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> Default for OneOrMany<T> {
+    fn default() -> Self {
+        OneOrMany::Many(Vec::new())
+    }
+}
+
+impl<T> OneOrMany<T> {
+    fn as_slice(&self) -> &[T] {
+        match self {
+            OneOrMany::One(one) => std::slice::from_ref(one),
+            OneOrMany::Many(many) => many,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawEmission {
+    strain: String,
+    chance: u8,
+}
+
+/// Every material in the simulation, indexed by [`Strain`].
+#[derive(Debug)]
+pub struct Materials {
+    materials: Vec<Material>,
+    ids: HashMap<String, Strain>,
+}
+
+impl Materials {
+    /// The materials in `resources/materials.toml`, parsed once and shared.
+    pub fn builtin() -> Arc<Materials> {
+        static BUILTIN_MATERIALS: OnceLock<Arc<Materials>> = OnceLock::new();
+
+        BUILTIN_MATERIALS
+            .get_or_init(|| {
+                Arc::new(Materials::parse(BUILTIN).expect("built-in materials are invalid"))
+            })
+            .clone()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Materials, MaterialError> {
+        Materials::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> Result<Materials, MaterialError> {
+        let raw: RawFile = toml::from_str(source)?;
+
+        if raw.material.len() > MAX_MATERIALS {
+            return Err(MaterialError::TooMany(raw.material.len()));
+        }
+        if raw.material.first().map(|m| m.id.as_str()) != Some("empty") {
+            return Err(MaterialError::MissingEmpty);
+        }
+
+        let mut ids = HashMap::new();
+        for (i, m) in raw.material.iter().enumerate() {
+            if ids.insert(m.id.clone(), Strain::from_id(i as u8)).is_some() {
+                return Err(MaterialError::DuplicateId(m.id.clone()));
+            }
+        }
+
+        let materials = raw
+            .material
+            .into_iter()
+            .map(|m| resolve(m, &ids))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Materials { materials, ids })
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+
+    /// Looks up a material by its id, e.g. `"sand"`.
+    pub fn find(&self, id: &str) -> Option<Strain> {
+        self.ids.get(id).copied()
+    }
+
+    /// The strain with this id, if it is registered.
+    pub fn strain(&self, id: u8) -> Option<Strain> {
+        if (id as usize) < self.materials.len() {
+            Some(Strain::from_id(id))
+        } else {
+            None
+        }
+    }
+
+    pub fn strains(&self) -> impl Iterator<Item = Strain> {
+        (0..self.materials.len()).map(|i| Strain::from_id(i as u8))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Material> {
+        self.materials.iter()
+    }
+
+    /// A hash of every material's properties, which is the same for identical sets on any
+    /// machine, to tell whether a recording was made with these materials.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        for m in self.materials.iter() {
+            m.hash(&mut hash);
+        }
+        hash.finish()
+    }
+}
+
+impl Index<Strain> for Materials {
+    type Output = Material;
+
+    fn index(&self, strain: Strain) -> &Material {
+        &self.materials[strain.id() as usize]
+    }
+}
+
+fn resolve(m: RawMaterial, ids: &HashMap<String, Strain>) -> Result<Material, MaterialError> {
+    let reference = |field: &'static str, target: &str| {
+        ids.get(target)
+            .copied()
+            .ok_or_else(|| MaterialError::UnknownReference {
+                material: m.id.clone(),
+                field,
+                target: target.to_string(),
+            })
+    };
+
+    let colour = match m.colour.as_deref() {
+        None => return Err(MaterialError::MissingColour(m.id.clone())),
+        Some(c) if c.iter().any(|v| !(0.0..=1.0).contains(v)) => {
+            return Err(MaterialError::InvalidColour(m.id.clone()))
+        }
+        Some(&[r, g, b]) => Colour { r, g, b, a: 1.0 },
+        Some(&[r, g, b, a]) => Colour { r, g, b, a },
+        Some(_) => return Err(MaterialError::InvalidColour(m.id.clone())),
+    };
+
+    if let Some((min, max)) = m.lifetime {
+        if min <= 0 || min >= max {
+            return Err(MaterialError::InvalidLifetime(m.id.clone()));
+        }
+    }
+
+    let dies_into = match &m.dies_into {
+        Some(target) => reference("dies_into", target)?,
+        None => Strain::EMPTY,
+    };
+
+    let percentage = |field: &'static str, value: u8| {
+        if value > 100 {
+            Err(MaterialError::InvalidPercentage {
+                material: m.id.clone(),
+                field,
+            })
+        } else {
+            Ok(value)
+        }
+    };
+
+    percentage("conductivity", m.conductivity)?;
+    percentage("splash", m.splash)?;
+    percentage("flow_chance", m.flow_chance)?;
+    percentage("ignite_chance", m.ignite_chance)?;
+
+    // a NaN would spread to every particle it conducts heat to
+    let temperatures = [
+        ("temperature", Some(m.temperature)),
+        ("burns_at", m.burns_at),
+        ("ignition_point", m.ignition_point),
+        ("melting_point", m.melting_point),
+        ("boiling_point", m.boiling_point),
+        ("freezing_point", m.freezing_point),
+    ];
+    for (field, value) in temperatures {
+        if value.is_some_and(|t| !t.is_finite()) {
+            return Err(MaterialError::InvalidTemperature {
+                material: m.id.clone(),
+                field,
+            });
+        }
+    }
+
+    // static materials are never displaced, so only moving ones need a density
+    let density = match (m.density, m.movement) {
+        (Some(density), _) => density,
+        (None, Movement::Static) => 0,
+        (None, _) => return Err(MaterialError::MissingDensity(m.id.clone())),
+    };
+
+    if m.dispersion > MAX_DISPERSION {
+        return Err(MaterialError::InvalidDispersion(m.id.clone()));
+    }
+
+    let transition = |field: &'static str, at: Option<f32>, into: &Option<String>| match (at, into)
+    {
+        (Some(at), Some(target)) => Ok(Some(Transition {
+            at,
+            into: reference(field, target)?,
+        })),
+        (Some(_), None) => Err(MaterialError::MissingTransition {
+            material: m.id.clone(),
+            field,
+        }),
+        (None, _) => Ok(None),
+    };
+
+    let ignition = transition("ignition_point", m.ignition_point, &m.ignites_into)?;
+    let melting = transition("melting_point", m.melting_point, &m.melts_into)?;
+    let boiling = transition("boiling_point", m.boiling_point, &m.boils_into)?;
+    let freezing = transition("freezing_point", m.freezing_point, &m.freezes_into)?;
+
+    let reactions = m
+        .reactions
+        .iter()
+        .map(|r| {
+            Ok(Reaction {
+                with: reference("reaction", &r.with)?,
+                chance: percentage("reaction chance", r.chance)?,
+                into: reference("reaction", &r.into)?,
+            })
+        })
+        .collect::<Result<_, MaterialError>>()?;
+
+    let emits_above = m
+        .emits_above
+        .as_slice()
+        .iter()
+        .map(|e| {
+            Ok(Emission {
+                strain: reference("emits_above", &e.strain)?,
+                chance: percentage("emits_above chance", e.chance)?,
+            })
+        })
+        .collect::<Result<_, MaterialError>>()?;
+
+    Ok(Material {
+        name: m.name.clone().unwrap_or_else(|| m.id.clone()),
+        colour,
+        density,
+        movement: m.movement,
+        flow_chance: m.flow_chance,
+        dispersion: m.dispersion,
+        splash: m.splash,
+        lifetime: m.lifetime,
+        dies_into,
+        temperature: m.temperature,
+        conductivity: m.conductivity,
+        burns_at: m.burns_at,
+        ignition,
+        ignite_chance: m.ignite_chance,
+        melting,
+        boiling,
+        freezing,
+        reactions,
+        emits_above,
+        id: m.id,
+    })
+}
@@ -0,0 +1,74 @@
+use nalgebra::Vector2;
+use rand::Rng;
+
+use crate::material::Materials;
+
+/// The temperature of empty space, which everything cools towards.
+pub const AMBIENT_TEMPERATURE: f32 = 20.0;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Particle {
+    pub strain: Strain,
+    pub(crate) update: bool,
+    pub lifetime: i16,
+    // in degrees
+    pub temperature: f32,
+    // in cells per tick, positive y is down
+    pub velocity: Vector2<f32>,
+}
+
+impl Default for Particle {
+    fn default() -> Self {
+        Particle {
+            strain: Strain::EMPTY,
+            update: false,
+            lifetime: -1,
+            temperature: AMBIENT_TEMPERATURE,
+            velocity: Vector2::zeros(),
+        }
+    }
+}
+
+// a particle of `strain` at its material's temperature, with its lifetime rolled from `rng`,
+// shared by edits to the world and particles spawned while stepping it
+pub(crate) fn fresh_particle<R: Rng>(
+    materials: &Materials,
+    strain: Strain,
+    rng: &mut R,
+) -> Particle {
+    let material = &materials[strain];
+
+    Particle {
+        strain,
+        lifetime: material.base_lifetime(rng),
+        temperature: material.base_temperature(),
+        ..Default::default()
+    }
+}
+
+// equal apart from the update flag
+pub(crate) fn same_state(a: &Particle, b: &Particle) -> bool {
+    a.strain == b.strain
+        && a.lifetime == b.lifetime
+        && a.temperature == b.temperature
+        && a.velocity == b.velocity
+}
+
+/// A material, as an index into the world's [`Materials`](crate::Materials).
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Strain(u8);
+
+impl Strain {
+    pub const EMPTY: Strain = Strain(0);
+
+    pub(crate) const fn from_id(id: u8) -> Strain {
+        Strain(id)
+    }
+
+    // stable id used when saving to disk and as the palette index
+    pub fn id(self) -> u8 {
+        self.0
+    }
+}
@@ -0,0 +1,484 @@
+//! Recordings of a session's input, tick by tick, that play back to the same world.
+//!
+//! Since every random decision is drawn from the world's seed, replaying the same input into a
+//! world created with the same seed reproduces the session exactly. Every
+//! [`CHECKPOINT_INTERVAL`] ticks, and at the end, the recording holds a [grid
+//! hash](World::grid_hash) to check the playback against.
+//!
+//! Layout, all integers little endian:
+//!
+//! | field       | type     |                                       |
+//! |-------------|----------|---------------------------------------|
+//! | magic       | `[u8;4]` | `b"FSRP"`                             |
+//! | version     | `u16`    | [`VERSION`]                           |
+//! | width       | `u32`    |                                       |
+//! | height      | `u32`    |                                       |
+//! | seed        | `u64`    |                                       |
+//! | undo budget | `u64`    | bytes                                 |
+//! | materials   | `u64`    | [fingerprint](Materials::fingerprint) |
+//! | frames      | ...      | repeated until the end of the file    |
+//!
+//! Each frame is its `u16` number of commands, each a `u8` tag (0 undo, 1 redo, 2 console,
+//! followed by a `u32` length and that many bytes of UTF-8, or 3 load, followed by a `u32`
+//! length and that many bytes of [snapshot](crate::snapshot)), then the `i32` cursor
+//! x and y, a `u8` of flags (1 button down, 2 has hash), the `u8` strain id, `u8` tool (0
+//! paint, 1 line, 2 rectangle, 3 filled rectangle, 4 fill), `u8` brush shape, `u8` brush
+//! radius and `u32` number of ticks stepped, followed by the `u64` hash if it has one.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::brush::{Brush, BrushShape, Drawing, Tool, ToolInput};
+use crate::history::History;
+use crate::material::Materials;
+use crate::snapshot::{read_array, truncated_or, MAX_CELLS};
+use crate::world::World;
+
+const MAGIC: &[u8; 4] = b"FSRP";
+pub const VERSION: u16 = 3;
+
+/// How many ticks apart the grid hashes in a recording are.
+pub const CHECKPOINT_INTERVAL: u64 = 60;
+
+const DOWN: u8 = 1;
+const HAS_HASH: u8 = 2;
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// The file does not start with the replay magic bytes.
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// The file ended partway through a frame.
+    Truncated,
+    UnknownStrain(u8),
+    /// The header describes an empty grid or one of more than [`MAX_CELLS`] cells.
+    InvalidDimensions {
+        width: u32,
+        height: u32,
+    },
+    /// The session was recorded with a different set of materials.
+    WrongMaterials,
+    /// A frame holds a tool, brush or command that doesn't exist.
+    Corrupt,
+    /// The world being played back into doesn't match the recording's checkpoint.
+    Desync {
+        tick: u64,
+        expected: u64,
+        found: u64,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "{}", e),
+            ReplayError::BadMagic => write!(f, "not a replay"),
+            ReplayError::UnsupportedVersion(v) => {
+                write!(f, "unsupported replay version {} (expected {})", v, VERSION)
+            }
+            ReplayError::Truncated => write!(f, "replay is truncated"),
+            ReplayError::UnknownStrain(id) => write!(f, "unknown strain id {}", id),
+            ReplayError::InvalidDimensions { width, height } => {
+                write!(f, "invalid replay dimensions {}x{}", width, height)
+            }
+            ReplayError::WrongMaterials => {
+                write!(f, "recorded with different materials to the ones loaded")
+            }
+            ReplayError::Corrupt => write!(f, "replay is corrupt"),
+            ReplayError::Desync {
+                tick,
+                expected,
+                found,
+            } => write!(
+                f,
+                "playback drifted from the recording by tick {} (grid hash {:016x}, expected {:016x})",
+                tick, found, expected
+            ),
+        }
+    }
+}
+
+impl Error for ReplayError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReplayError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReplayError {
+    fn from(e: io::Error) -> Self {
+        truncated_or(e, ReplayError::Truncated, ReplayError::Io)
+    }
+}
+
+/// Something done to the world other than with a tool.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    Undo,
+    Redo,
+    /// A line typed into the console, which is recorded in the history before it runs.
+    Console(String),
+    /// A snapshot loaded into the world, like a console `load`, but held in the recording so
+    /// playing it back doesn't depend on the file.
+    Load(Vec<u8>),
+}
+
+/// One update of a session: the commands run, then the tool used, then the world stepped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub commands: Vec<Command>,
+    pub input: ToolInput,
+    pub steps: u32,
+    /// The world's grid hash after the frame.
+    pub hash: Option<u64>,
+}
+
+/// A recorded session.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub width: usize,
+    pub height: usize,
+    pub seed: u64,
+    pub undo_budget: usize,
+    pub frames: Vec<Frame>,
+}
+
+impl Replay {
+    pub fn load<P: AsRef<Path>>(path: P, materials: &Materials) -> Result<Replay, ReplayError> {
+        read_replay(&mut BufReader::new(File::open(path)?), materials)
+    }
+
+    /// A world like the one the session was recorded in, before anything was done to it.
+    pub fn world(&self, materials: Arc<Materials>) -> World {
+        World::with_materials(self.width, self.height, self.seed, materials)
+    }
+
+    /// Plays the frames back one at a time.
+    pub fn playback(&self) -> Playback<'_> {
+        Playback {
+            frames: self.frames.iter(),
+            frame: None,
+            history: History::new(self.undo_budget),
+            drawing: Drawing::default(),
+            ticks: 0,
+        }
+    }
+}
+
+/// Feeds a replay's frames into a world. For each frame, [`Playback::edit`] makes its edits,
+/// the world is stepped however many ticks it returns, and then [`Playback::verify`] checks
+/// the world against the recording.
+pub struct Playback<'a> {
+    frames: std::slice::Iter<'a, Frame>,
+    frame: Option<&'a Frame>,
+    history: History,
+    drawing: Drawing,
+    ticks: u64,
+}
+
+impl<'a> Playback<'a> {
+    /// Runs the next frame's commands, passing console lines to `console`, and uses its tool,
+    /// returning how many ticks to step the world afterwards, or `None` once every frame has
+    /// been played.
+    pub fn edit(
+        &mut self,
+        world: &mut World,
+        mut console: impl FnMut(&mut World, &str),
+    ) -> Option<u32> {
+        let frame = self.frames.next()?;
+        self.frame = Some(frame);
+
+        for command in frame.commands.iter() {
+            match command {
+                Command::Undo => {
+                    self.history.undo(world);
+                }
+                Command::Redo => {
+                    self.history.redo(world);
+                }
+                Command::Console(line) => {
+                    self.history.record(world);
+                    console(world, line);
+                }
+                Command::Load(snapshot) => {
+                    self.history.record(world);
+                    // a load that failed while recording fails the same way here
+                    let _ = world.read_into(&mut &snapshot[..]);
+                }
+            }
+        }
+
+        self.drawing.update(world, &mut self.history, &frame.input);
+        self.ticks += frame.steps as u64;
+
+        Some(frame.steps)
+    }
+
+    /// Checks the world, once stepped, against the hash recorded for the frame, if it has one.
+    pub fn verify(&self, world: &World) -> Result<(), ReplayError> {
+        match self.frame.and_then(|f| f.hash) {
+            Some(expected) if world.grid_hash() != expected => Err(ReplayError::Desync {
+                tick: self.ticks,
+                expected,
+                found: world.grid_hash(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// How many ticks have been played.
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+}
+
+/// Writes a session to a replay file as it happens.
+pub struct Recorder {
+    writer: BufWriter<File>,
+    commands: Vec<Command>,
+    // held back until the next one, so the last can be given the final hash
+    last: Option<Frame>,
+    ticks: u64,
+}
+
+impl Recorder {
+    /// Starts recording to `path` a session in `world`, which must be as it was created, apart
+    /// from anything a script played back with the recording does to it when loaded.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        world: &World,
+        undo_budget: usize,
+    ) -> Result<Recorder, ReplayError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(world.width() as u32).to_le_bytes())?;
+        writer.write_all(&(world.height() as u32).to_le_bytes())?;
+        writer.write_all(&world.seed().to_le_bytes())?;
+        writer.write_all(&(undo_budget as u64).to_le_bytes())?;
+        writer.write_all(&world.materials().fingerprint().to_le_bytes())?;
+
+        Ok(Recorder {
+            writer,
+            commands: Vec::new(),
+            last: None,
+            ticks: 0,
+        })
+    }
+
+    /// Adds a command to the frame being recorded, to be run before its tool is used.
+    pub fn command(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+
+    /// Finishes the frame being recorded, in which the tool was used with `input` and then
+    /// `world` was stepped `steps` times.
+    pub fn frame(&mut self, input: &ToolInput, steps: u32, world: &World) -> io::Result<()> {
+        let before = self.ticks;
+        self.ticks += steps as u64;
+        let checkpoint = before / CHECKPOINT_INTERVAL != self.ticks / CHECKPOINT_INTERVAL;
+
+        let frame = Frame {
+            commands: std::mem::take(&mut self.commands),
+            input: *input,
+            steps,
+            hash: if checkpoint {
+                Some(world.grid_hash())
+            } else {
+                None
+            },
+        };
+
+        if let Some(last) = self.last.replace(frame) {
+            write_frame(&mut self.writer, &last)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the last frame with `world`'s hash, as it is at the end of the session.
+    pub fn finish(mut self, world: &World) -> io::Result<()> {
+        if let Some(mut last) = self.last.take() {
+            last.hash = Some(world.grid_hash());
+            write_frame(&mut self.writer, &last)?;
+        }
+        self.writer.flush()
+    }
+}
+
+fn write_frame<W: Write>(w: &mut W, frame: &Frame) -> io::Result<()> {
+    w.write_all(&(frame.commands.len() as u16).to_le_bytes())?;
+    for command in frame.commands.iter() {
+        match command {
+            Command::Undo => w.write_all(&[0])?,
+            Command::Redo => w.write_all(&[1])?,
+            Command::Console(line) => {
+                w.write_all(&[2])?;
+                w.write_all(&(line.len() as u32).to_le_bytes())?;
+                w.write_all(line.as_bytes())?;
+            }
+            Command::Load(snapshot) => {
+                w.write_all(&[3])?;
+                w.write_all(&(snapshot.len() as u32).to_le_bytes())?;
+                w.write_all(snapshot)?;
+            }
+        }
+    }
+
+    let input = &frame.input;
+    let mut flags = 0;
+    if input.down {
+        flags |= DOWN;
+    }
+    if frame.hash.is_some() {
+        flags |= HAS_HASH;
+    }
+    let tool = match input.tool {
+        Tool::Paint => 0,
+        Tool::Line => 1,
+        Tool::Rect { filled: false } => 2,
+        Tool::Rect { filled: true } => 3,
+        Tool::Fill => 4,
+    };
+    let shape = BrushShape::ALL
+        .iter()
+        .position(|&s| s == input.brush.shape)
+        .unwrap() as u8;
+
+    w.write_all(&(input.cursor.0 as i32).to_le_bytes())?;
+    w.write_all(&(input.cursor.1 as i32).to_le_bytes())?;
+    w.write_all(&[
+        flags,
+        input.strain.id(),
+        tool,
+        shape,
+        input.brush.radius as u8,
+    ])?;
+    w.write_all(&frame.steps.to_le_bytes())?;
+    if let Some(hash) = frame.hash {
+        w.write_all(&hash.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+pub fn read_replay<R: Read>(r: &mut R, materials: &Materials) -> Result<Replay, ReplayError> {
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(ReplayError::BadMagic);
+    }
+
+    let version = u16::from_le_bytes(read_array(r)?);
+    if version != VERSION {
+        return Err(ReplayError::UnsupportedVersion(version));
+    }
+
+    let width = u32::from_le_bytes(read_array(r)?);
+    let height = u32::from_le_bytes(read_array(r)?);
+    match (width as usize).checked_mul(height as usize) {
+        Some(cells) if cells > 0 && cells <= MAX_CELLS => {}
+        _ => return Err(ReplayError::InvalidDimensions { width, height }),
+    }
+
+    let mut replay = Replay {
+        width: width as usize,
+        height: height as usize,
+        seed: u64::from_le_bytes(read_array(r)?),
+        undo_budget: u64::from_le_bytes(read_array(r)?) as usize,
+        frames: Vec::new(),
+    };
+    if u64::from_le_bytes(read_array(r)?) != materials.fingerprint() {
+        return Err(ReplayError::WrongMaterials);
+    }
+
+    // the end of the file is only expected between frames
+    loop {
+        let mut count = [0; 2];
+        match r.read(&mut count[..1])? {
+            0 => return Ok(replay),
+            _ => r.read_exact(&mut count[1..])?,
+        }
+
+        replay
+            .frames
+            .push(read_frame(r, u16::from_le_bytes(count), materials)?);
+    }
+}
+
+fn read_frame<R: Read>(
+    r: &mut R,
+    commands: u16,
+    materials: &Materials,
+) -> Result<Frame, ReplayError> {
+    let commands = (0..commands)
+        .map(|_| match read_array(r)? {
+            [0] => Ok(Command::Undo),
+            [1] => Ok(Command::Redo),
+            [2] => String::from_utf8(read_bytes(r)?)
+                .map(Command::Console)
+                .map_err(|_| ReplayError::Corrupt),
+            [3] => Ok(Command::Load(read_bytes(r)?)),
+            _ => Err(ReplayError::Corrupt),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let x = i32::from_le_bytes(read_array(r)?);
+    let y = i32::from_le_bytes(read_array(r)?);
+    let [flags, id, tool, shape, radius] = read_array(r)?;
+    let steps = u32::from_le_bytes(read_array(r)?);
+    let hash = if flags & HAS_HASH != 0 {
+        Some(u64::from_le_bytes(read_array(r)?))
+    } else {
+        None
+    };
+
+    let tool = match tool {
+        0 => Tool::Paint,
+        1 => Tool::Line,
+        2 => Tool::Rect { filled: false },
+        3 => Tool::Rect { filled: true },
+        4 => Tool::Fill,
+        _ => return Err(ReplayError::Corrupt),
+    };
+    let shape = *BrushShape::ALL
+        .get(shape as usize)
+        .ok_or(ReplayError::Corrupt)?;
+    if radius as usize > Brush::MAX_RADIUS {
+        return Err(ReplayError::Corrupt);
+    }
+
+    Ok(Frame {
+        commands,
+        input: ToolInput {
+            cursor: (x as isize, y as isize),
+            down: flags & DOWN != 0,
+            tool,
+            brush: Brush {
+                shape,
+                radius: radius as usize,
+            },
+            strain: materials.strain(id).ok_or(ReplayError::UnknownStrain(id))?,
+        },
+        steps,
+        hash,
+    })
+}
+
+// a `u32` length and that many bytes
+fn read_bytes<R: Read>(r: &mut R) -> Result<Vec<u8>, ReplayError> {
+    let len = u32::from_le_bytes(read_array(r)?) as usize;
+    let mut bytes = Vec::new();
+    r.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < len {
+        return Err(ReplayError::Truncated);
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,353 @@
+//! Rhai scripts for scripted scenarios and materials with custom behaviour.
+//!
+//! A script's top level runs once when it is loaded, so it can lay out a scene. After that it
+//! is run alongside the world with [`Script::tick`] after every tick, which calls the script's
+//! `fn on_tick(tick)` if it has one, followed by every function handed to `on_update` for each
+//! particle of its material:
+//!
+//! ```rhai
+//! fill_row(0, "water");
+//!
+//! on_update("acid", |x, y| {
+//!     if y + 1 < height() && get(x, y + 1) != "empty" && chance(20) {
+//!         set(x, y + 1, "empty");
+//!         set(x, y, "empty");
+//!     }
+//! });
+//!
+//! fn fill_row(y, material) {
+//!     for x in 0..width() {
+//!         set(x, y, material);
+//!     }
+//! }
+//!
+//! fn on_tick(tick) {
+//!     if tick % 10 == 0 {
+//!         spawn_particle(width() / 2, 0, "sand");
+//!     }
+//! }
+//! ```
+//!
+//! Materials are named by their ids, so a new strain is prototyped by adding it to a materials
+//! file as a static material and giving it an update function. Scripts reach the world through:
+//!
+//! - `width()` and `height()`
+//! - `get(x, y)`, the id of the material at (x, y), or `()` off the grid
+//! - `temperature(x, y)`, or `()` off the grid
+//! - `set(x, y, material)`, which replaces a cell with a fresh particle
+//! - `spawn_particle(x, y, material)`, which does the same but ignores cells off the grid
+//! - `chance(percent)` and `random(n)`, drawn from the world's rng so runs stay reproducible
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use rand::Rng;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FnPtr, ParseError, AST, INT};
+
+use crate::particle::Strain;
+use crate::world::World;
+
+type Slot<T> = Rc<RefCell<T>>;
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(io::Error),
+    Parse(ParseError),
+    /// The script failed while running.
+    Run(Box<EvalAltResult>),
+    /// A run took more than [`Script::MAX_OPERATIONS`].
+    TooManyOperations,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io(e) => write!(f, "{}", e),
+            ScriptError::Parse(e) => write!(f, "{}", e),
+            ScriptError::Run(e) => write!(f, "{}", e),
+            ScriptError::TooManyOperations => write!(
+                f,
+                "script ran for more than {} operations without finishing",
+                Script::MAX_OPERATIONS
+            ),
+        }
+    }
+}
+
+impl Error for ScriptError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ScriptError::Io(e) => Some(e),
+            ScriptError::Parse(e) => Some(e),
+            ScriptError::Run(e) => Some(&**e),
+            ScriptError::TooManyOperations => None,
+        }
+    }
+}
+
+impl From<io::Error> for ScriptError {
+    fn from(e: io::Error) -> Self {
+        ScriptError::Io(e)
+    }
+}
+
+impl From<ParseError> for ScriptError {
+    fn from(e: ParseError) -> Self {
+        ScriptError::Parse(e)
+    }
+}
+
+impl From<Box<EvalAltResult>> for ScriptError {
+    fn from(e: Box<EvalAltResult>) -> Self {
+        match *e {
+            EvalAltResult::ErrorTooManyOperations(_) => ScriptError::TooManyOperations,
+            _ => ScriptError::Run(e),
+        }
+    }
+}
+
+/// A compiled script and the hooks it registered.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    // the world the script is being run with, lent to it for the length of each run
+    world: Slot<Option<World>>,
+    updates: Slot<Vec<(Strain, FnPtr)>>,
+    has_on_tick: bool,
+    ticks: INT,
+}
+
+impl Script {
+    /// How many operations the script's top level, or one call of a hook, may take before it
+    /// is stopped, so a runaway loop can't hang the game.
+    pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+    /// Reads and compiles the script at `path`, then runs its top level against `world`.
+    pub fn load<P: AsRef<Path>>(path: P, world: &mut World) -> Result<Script, ScriptError> {
+        Script::compile(&fs::read_to_string(path)?, world)
+    }
+
+    /// Compiles `source`, then runs its top level against `world`.
+    pub fn compile(source: &str, world: &mut World) -> Result<Script, ScriptError> {
+        let world_slot: Slot<Option<World>> = Rc::default();
+        let updates: Slot<Vec<(Strain, FnPtr)>> = Rc::default();
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(Script::MAX_OPERATIONS);
+        register_world(&mut engine, &world_slot);
+
+        let (w, u) = (Rc::clone(&world_slot), Rc::clone(&updates));
+        engine.register_fn(
+            "on_update",
+            move |material: &str, f: FnPtr| -> Result<(), Box<EvalAltResult>> {
+                let strain = with_world(&w, |world| find(world, material))?;
+                u.borrow_mut().push((strain, f));
+                Ok(())
+            },
+        );
+
+        let ast = engine.compile(source)?;
+        let has_on_tick = ast
+            .iter_functions()
+            .any(|f| f.name == "on_tick" && f.params.len() == 1);
+
+        let script = Script {
+            engine,
+            ast,
+            world: world_slot,
+            updates,
+            has_on_tick,
+            ticks: 0,
+        };
+
+        script.lend(world, |s| s.engine.run_ast(&s.ast))?;
+        Ok(script)
+    }
+
+    /// Runs the script's hooks for the tick `world` has just been stepped through: `on_tick`,
+    /// then the update function of every particle with one, in row-major order.
+    pub fn tick(&mut self, world: &mut World) -> Result<(), ScriptError> {
+        self.ticks += 1;
+        let tick = self.ticks;
+
+        let result = self.lend(world, |s| {
+            if s.has_on_tick {
+                // the top level already ran when the script was loaded, and whatever the hooks
+                // return is ignored
+                let options = CallFnOptions::new().eval_ast(false);
+                let _ = s.engine.call_fn_with_options::<Dynamic>(
+                    options,
+                    &mut Default::default(),
+                    &s.ast,
+                    "on_tick",
+                    (tick,),
+                )?;
+            }
+
+            // cloned so an update function can register others without a double borrow
+            let updates = s.updates.borrow().clone();
+            if updates.is_empty() {
+                return Ok(());
+            }
+
+            // gathered up front so particles moved by an update aren't updated twice
+            let scripted: Vec<(INT, INT, usize)> = with_world(&s.world, |world| {
+                let width = world.width();
+                world
+                    .cells()
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, p)| {
+                        let u = updates.iter().position(|(strain, _)| *strain == p.strain)?;
+                        Some(((i % width) as INT, (i / width) as INT, u))
+                    })
+                    .collect()
+            });
+
+            for (x, y, u) in scripted {
+                let (strain, f) = &updates[u];
+
+                // skip particles destroyed or replaced by earlier updates this tick
+                if with_world(&s.world, |world| world.get(x as usize, y as usize).strain) != *strain
+                {
+                    continue;
+                }
+
+                let _ = f.call::<Dynamic>(&s.engine, &s.ast, (x, y))?;
+            }
+
+            Ok(())
+        });
+
+        Ok(result?)
+    }
+
+    // Moves `world` into the script's slot for as long as `run` takes, where the functions
+    // registered with the engine can reach it.
+    fn lend<T>(
+        &self,
+        world: &mut World,
+        run: impl FnOnce(&Script) -> Result<T, Box<EvalAltResult>>,
+    ) -> Result<T, Box<EvalAltResult>> {
+        let stand_in = World::with_materials(0, 0, 0, Arc::clone(world.materials()));
+        *self.world.borrow_mut() = Some(mem::replace(world, stand_in));
+
+        let result = run(self);
+
+        *world = self
+            .world
+            .borrow_mut()
+            .take()
+            .expect("the world is returned");
+        result
+    }
+}
+
+fn with_world<T>(slot: &Slot<Option<World>>, f: impl FnOnce(&mut World) -> T) -> T {
+    f(slot
+        .borrow_mut()
+        .as_mut()
+        .expect("scripts only run while holding a world"))
+}
+
+fn find(world: &World, material: &str) -> Result<Strain, Box<EvalAltResult>> {
+    world
+        .materials()
+        .find(material)
+        .ok_or_else(|| format!("no material `{}`", material).into())
+}
+
+// The cell at (x, y), if it's on the grid
+fn cell(world: &World, x: INT, y: INT) -> Option<(usize, usize)> {
+    let (x, y) = (usize::try_from(x).ok()?, usize::try_from(y).ok()?);
+    if world.in_bounds(x, y) {
+        Some((x, y))
+    } else {
+        None
+    }
+}
+
+fn register_world(engine: &mut Engine, slot: &Slot<Option<World>>) {
+    let w = Rc::clone(slot);
+    engine.register_fn("width", move || {
+        with_world(&w, |world| world.width() as INT)
+    });
+
+    let w = Rc::clone(slot);
+    engine.register_fn("height", move || {
+        with_world(&w, |world| world.height() as INT)
+    });
+
+    let w = Rc::clone(slot);
+    engine.register_fn("get", move |x: INT, y: INT| {
+        with_world(&w, |world| match cell(world, x, y) {
+            Some((x, y)) => {
+                let strain = world.get(x, y).strain;
+                Dynamic::from(world.materials()[strain].id.clone())
+            }
+            None => Dynamic::UNIT,
+        })
+    });
+
+    let w = Rc::clone(slot);
+    engine.register_fn("temperature", move |x: INT, y: INT| {
+        with_world(&w, |world| match cell(world, x, y) {
+            Some((x, y)) => Dynamic::from_float(world.get(x, y).temperature as rhai::FLOAT),
+            None => Dynamic::UNIT,
+        })
+    });
+
+    let w = Rc::clone(slot);
+    engine.register_fn(
+        "set",
+        move |x: INT, y: INT, material: &str| -> Result<(), Box<EvalAltResult>> {
+            with_world(&w, |world| {
+                let strain = find(world, material)?;
+                let (x, y) = cell(world, x, y)
+                    .ok_or_else(|| format!("({}, {}) is outside the world", x, y))?;
+                let p = world.create_particle(strain);
+                world.set(x, y, p);
+                Ok(())
+            })
+        },
+    );
+
+    let w = Rc::clone(slot);
+    engine.register_fn(
+        "spawn_particle",
+        move |x: INT, y: INT, material: &str| -> Result<(), Box<EvalAltResult>> {
+            with_world(&w, |world| {
+                let strain = find(world, material)?;
+                if let Some((x, y)) = cell(world, x, y) {
+                    let p = world.create_particle(strain);
+                    world.spawn_particle(x, y, p);
+                }
+                Ok(())
+            })
+        },
+    );
+
+    let w = Rc::clone(slot);
+    engine.register_fn("chance", move |percent: INT| {
+        with_world(&w, |world| world.rng().gen_range(0, 100) < percent)
+    });
+
+    let w = Rc::clone(slot);
+    engine.register_fn("random", move |n: INT| {
+        with_world(&w, |world| {
+            if n > 0 {
+                world.rng().gen_range(0, n)
+            } else {
+                0
+            }
+        })
+    });
+}
@@ -0,0 +1,281 @@
+//! Binary world snapshots.
+//!
+//! Layout, all integers little endian:
+//!
+//! | field   | type     |                                          |
+//! |---------|----------|------------------------------------------|
+//! | magic   | `[u8;4]` | `b"FSND"`                                |
+//! | version | `u16`    | [`VERSION`]                              |
+//! | width   | `u32`    |                                          |
+//! | height  | `u32`    |                                          |
+//! | runs    | ...      | repeated until `width * height` cells    |
+//!
+//! Each run is a `u32` length followed by the `u8` strain id, `i16` lifetime, `f32`
+//! temperature and two `f32` velocity components shared by that many consecutive cells, in
+//! row-major order. Version 1 snapshots have no temperature and load with every particle at its
+//! material's starting temperature, and versions before 3 have no velocity and load at rest.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use nalgebra::Vector2;
+use rand::random;
+
+use crate::material::Materials;
+use crate::particle::Particle;
+use crate::world::World;
+
+const MAGIC: &[u8; 4] = b"FSND";
+pub const VERSION: u16 = 3;
+
+/// The most cells a snapshot can hold, 4096 x 4096 worth, which caps what a corrupt header can
+/// make a load allocate.
+pub const MAX_CELLS: usize = 1 << 24;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The file does not start with the snapshot magic bytes.
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// The file ended before every cell of the grid was read.
+    Truncated,
+    UnknownStrain(u8),
+    /// The header describes an empty grid or one of more than [`MAX_CELLS`] cells.
+    InvalidDimensions {
+        width: u32,
+        height: u32,
+    },
+    /// A run extends past the last cell of the grid.
+    RunOverflow,
+    /// The snapshot is a different size to the world it was loaded into.
+    DimensionMismatch {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::BadMagic => write!(f, "not a world snapshot"),
+            SnapshotError::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "unsupported snapshot version {} (expected 1 - {})",
+                    v, VERSION
+                )
+            }
+            SnapshotError::Truncated => write!(f, "snapshot is truncated"),
+            SnapshotError::UnknownStrain(id) => write!(f, "unknown strain id {}", id),
+            SnapshotError::InvalidDimensions { width, height } => {
+                write!(f, "invalid snapshot dimensions {}x{}", width, height)
+            }
+            SnapshotError::RunOverflow => write!(f, "run extends past the end of the grid"),
+            SnapshotError::DimensionMismatch { expected, found } => write!(
+                f,
+                "snapshot is {}x{} but the world is {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        truncated_or(e, SnapshotError::Truncated, SnapshotError::Io)
+    }
+}
+
+// `truncated` if the file ended early, since a short file is corrupt rather than unreadable,
+// and any other io error wrapped in `io`
+pub(crate) fn truncated_or<E>(e: io::Error, truncated: E, io: impl FnOnce(io::Error) -> E) -> E {
+    if e.kind() == io::ErrorKind::UnexpectedEof {
+        truncated
+    } else {
+        io(e)
+    }
+}
+
+impl World {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        write_snapshot(self, &mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Loads a snapshot saved with the built-in materials.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<World, SnapshotError> {
+        World::load_with_materials(path, Materials::builtin())
+    }
+
+    pub fn load_with_materials<P: AsRef<Path>>(
+        path: P,
+        materials: Arc<Materials>,
+    ) -> Result<World, SnapshotError> {
+        read_snapshot(&mut BufReader::new(File::open(path)?), materials)
+    }
+
+    /// Replaces this world's grid with the snapshot at `path`, keeping its seed, rng and
+    /// materials.
+    pub fn load_into<P: AsRef<Path>>(&mut self, path: P) -> Result<(), SnapshotError> {
+        self.read_into(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Like [`World::load_into`], but reads the snapshot from `r`.
+    pub fn read_into<R: Read>(&mut self, r: &mut R) -> Result<(), SnapshotError> {
+        let loaded = read_snapshot(r, Arc::clone(self.materials()))?;
+
+        let expected = (self.width(), self.height());
+        let found = (loaded.width(), loaded.height());
+        if expected != found {
+            return Err(SnapshotError::DimensionMismatch { expected, found });
+        }
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                self.set(x, y, loaded.get(x, y));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn write_snapshot<W: Write>(world: &World, w: &mut W) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&VERSION.to_le_bytes())?;
+    w.write_all(&(world.width() as u32).to_le_bytes())?;
+    w.write_all(&(world.height() as u32).to_le_bytes())?;
+
+    let mut run: Option<(u32, Particle)> = None;
+
+    for y in 0..world.height() {
+        for x in 0..world.width() {
+            let p = world.get(x, y);
+
+            run = match run {
+                Some((len, first))
+                    if first.strain == p.strain
+                        && first.lifetime == p.lifetime
+                        && first.temperature == p.temperature
+                        && first.velocity == p.velocity =>
+                {
+                    Some((len + 1, first))
+                }
+                _ => {
+                    if let Some(r) = run {
+                        write_run(w, r)?;
+                    }
+                    Some((1, p))
+                }
+            };
+        }
+    }
+
+    if let Some(r) = run {
+        write_run(w, r)?;
+    }
+
+    Ok(())
+}
+
+fn write_run<W: Write>(w: &mut W, (len, p): (u32, Particle)) -> io::Result<()> {
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(&[p.strain.id()])?;
+    w.write_all(&p.lifetime.to_le_bytes())?;
+    w.write_all(&p.temperature.to_le_bytes())?;
+    w.write_all(&p.velocity.x.to_le_bytes())?;
+    w.write_all(&p.velocity.y.to_le_bytes())
+}
+
+pub fn read_snapshot<R: Read>(
+    r: &mut R,
+    materials: Arc<Materials>,
+) -> Result<World, SnapshotError> {
+    let mut magic = [0; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(SnapshotError::BadMagic);
+    }
+
+    let version = u16::from_le_bytes(read_array(r)?);
+    if version == 0 || version > VERSION {
+        return Err(SnapshotError::UnsupportedVersion(version));
+    }
+
+    let width = u32::from_le_bytes(read_array(r)?);
+    let height = u32::from_le_bytes(read_array(r)?);
+    let cells = match (width as usize).checked_mul(height as usize) {
+        Some(cells) if cells > 0 && cells <= MAX_CELLS => cells,
+        _ => return Err(SnapshotError::InvalidDimensions { width, height }),
+    };
+
+    // grown as runs arrive rather than allocated from the header, so a truncated or corrupt
+    // file fails before claiming the memory its header asks for
+    let mut grid: Vec<Particle> = Vec::new();
+
+    while grid.len() < cells {
+        let len = u32::from_le_bytes(read_array(r)?) as usize;
+        let [id] = read_array(r)?;
+        let lifetime = i16::from_le_bytes(read_array(r)?);
+        let temperature = if version >= 2 {
+            Some(f32::from_le_bytes(read_array(r)?))
+        } else {
+            None
+        };
+        let velocity = if version >= 3 {
+            Vector2::new(
+                f32::from_le_bytes(read_array(r)?),
+                f32::from_le_bytes(read_array(r)?),
+            )
+        } else {
+            Vector2::zeros()
+        };
+
+        let strain = materials
+            .strain(id)
+            .ok_or(SnapshotError::UnknownStrain(id))?;
+        if len > cells - grid.len() {
+            return Err(SnapshotError::RunOverflow);
+        }
+
+        let p = Particle {
+            strain,
+            lifetime,
+            temperature: temperature.unwrap_or_else(|| materials[strain].base_temperature()),
+            velocity,
+            ..Default::default()
+        };
+        grid.resize(grid.len() + len, p);
+    }
+
+    let mut world = World::with_materials(width as usize, height as usize, random(), materials);
+    world.restore(&grid);
+
+    Ok(world)
+}
+
+// `N` bytes, failing with `UnexpectedEof` if the reader runs out first
+pub(crate) fn read_array<R: Read, const N: usize>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
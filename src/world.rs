@@ -0,0 +1,292 @@
+use std::sync::Arc;
+
+use rand::*;
+use rand_pcg::Pcg64Mcg;
+use rayon::prelude::*;
+
+use crate::chunk::{step_chunks, ChunkState, Chunks, GRAVITY};
+use crate::hash::Fnv1a;
+use crate::material::Materials;
+use crate::particle::{fresh_particle, Particle, Strain, AMBIENT_TEMPERATURE};
+
+pub(crate) const FOUR_ADJ_PARTICLES: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+// the share of a temperature difference that crosses between two fully conductive neighbours
+// each tick, kept at or below 1/4 so a cell never overshoots the average of its neighbours
+const HEAT_FLOW: f32 = 0.25;
+// the share of its difference from ambient that empty space loses each tick
+const AIR_COOLING: f32 = 0.125;
+// how far a temperature has to move in a tick to keep its chunk awake
+const HEAT_SETTLED: f32 = 0.1;
+
+/// The particle grid and the rules that step it forward.
+pub struct World {
+    grid: Vec<Particle>,
+    grid_width: usize,
+    grid_height: usize,
+    update: bool,
+    particles_updated: u64,
+    seed: u64,
+    rng: Pcg64Mcg,
+    materials: Arc<Materials>,
+    // scratch buffer for heat transfer, kept to avoid reallocating every tick
+    heat: Vec<f32>,
+    chunks: Chunks,
+    gravity: f32,
+}
+
+impl World {
+    /// An empty world with a randomly chosen seed.
+    pub fn new(width: usize, height: usize) -> World {
+        World::with_seed(width, height, random())
+    }
+
+    /// An empty world whose every random decision is drawn from `seed`, so the same seed and
+    /// the same sequence of edits always produce the same grid.
+    pub fn with_seed(width: usize, height: usize, seed: u64) -> World {
+        World::with_materials(width, height, seed, Materials::builtin())
+    }
+
+    /// An empty, seeded world simulating `materials` instead of the built-in set.
+    pub fn with_materials(
+        width: usize,
+        height: usize,
+        seed: u64,
+        materials: Arc<Materials>,
+    ) -> World {
+        World {
+            grid: vec![Particle::default(); width * height],
+            grid_width: width,
+            grid_height: height,
+            update: false,
+            particles_updated: 0,
+            seed,
+            rng: Pcg64Mcg::seed_from_u64(seed),
+            materials,
+            heat: Vec::with_capacity(width * height),
+            chunks: Chunks::new(width, height),
+            gravity: GRAVITY,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restarts the world's rng from `seed`, leaving the grid as it is.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = Pcg64Mcg::seed_from_u64(seed);
+    }
+
+    pub(crate) fn rng(&mut self) -> &mut Pcg64Mcg {
+        &mut self.rng
+    }
+
+    pub fn materials(&self) -> &Arc<Materials> {
+        &self.materials
+    }
+
+    pub fn width(&self) -> usize {
+        self.grid_width
+    }
+
+    pub fn height(&self) -> usize {
+        self.grid_height
+    }
+
+    /// How much faster falling particles get each tick, in cells per tick.
+    pub fn gravity(&self) -> f32 {
+        self.gravity
+    }
+
+    /// Sets how much faster falling particles get each tick. A negative gravity turns the world
+    /// upside down: powders and liquids fall upwards and gases and flames sink. Particles start
+    /// falling at one cell per tick whatever the gravity, so at zero they fall at that steady
+    /// speed.
+    ///
+    /// Panics if `gravity` is infinite or NaN.
+    pub fn set_gravity(&mut self, gravity: f32) {
+        assert!(gravity.is_finite(), "gravity must be finite");
+        self.gravity = gravity;
+        self.chunks.wake_all();
+    }
+
+    /// How many particles were processed by the last call to [`World::step`].
+    pub fn particles_updated(&self) -> u64 {
+        self.particles_updated
+    }
+
+    /// How many cells hold each strain, indexed by strain id.
+    pub fn strain_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.materials.len()];
+        for p in self.grid.iter() {
+            counts[p.strain.id() as usize] += 1;
+        }
+        counts
+    }
+
+    /// A hash of every cell's strain, lifetime, temperature and velocity, which is the same
+    /// for identical grids on any machine, to check that two runs haven't drifted apart.
+    pub fn grid_hash(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        for p in self.grid.iter() {
+            hash.write(&[p.strain.id()]);
+            hash.write(&p.lifetime.to_le_bytes());
+            hash.write_f32(p.temperature);
+            hash.write_f32(p.velocity.x);
+            hash.write_f32(p.velocity.y);
+        }
+        hash.finish()
+    }
+
+    /// Every chunk of the grid and whether it will be simulated next tick. Chunks where
+    /// nothing is happening sleep until something nearby changes or they are edited.
+    pub fn chunks(&self) -> impl Iterator<Item = ChunkState> + '_ {
+        self.chunks.states()
+    }
+
+    /// Wakes every chunk, e.g. after the materials' behaviour has changed underneath them.
+    pub fn wake_all(&mut self) {
+        self.chunks.wake_all();
+    }
+
+    pub fn in_bounds(&self, x: usize, y: usize) -> bool {
+        x < self.grid_width && y < self.grid_height
+    }
+
+    pub fn set_row(&mut self, strain: Strain, row: usize) {
+        for x in 0..self.grid_width {
+            self.set_strain(x, row, strain);
+        }
+    }
+
+    pub(crate) fn cells(&self) -> &[Particle] {
+        &self.grid
+    }
+
+    /// Replaces every cell of the grid with `cells`, in row-major order.
+    pub(crate) fn restore(&mut self, cells: &[Particle]) {
+        self.grid.copy_from_slice(cells);
+        self.chunks.wake_all();
+    }
+
+    fn index(&self, x: usize, y: usize) -> usize {
+        x + self.grid_width * y
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Particle {
+        self.grid[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, p: Particle) {
+        let i: usize = self.index(x, y);
+
+        self.grid[i] = p;
+        self.chunks.wake_around(x, y);
+    }
+
+    pub fn set_strain(&mut self, x: usize, y: usize, s: Strain) {
+        let i: usize = self.index(x, y);
+
+        self.grid[i].strain = s;
+        self.chunks.wake_around(x, y);
+    }
+
+    pub fn is_particle_empty(&self, x: usize, y: usize) -> bool {
+        self.get(x, y).strain == Strain::EMPTY
+    }
+
+    /// A fresh particle of `strain` at its material's temperature, with its lifetime rolled
+    /// from the world's rng.
+    pub fn create_particle(&mut self, strain: Strain) -> Particle {
+        fresh_particle(&self.materials, strain, &mut self.rng)
+    }
+
+    /// Places `p` at (x, y), ignoring positions outside the grid.
+    pub fn spawn_particle(&mut self, x: usize, y: usize, p: Particle) {
+        if self.in_bounds(x, y) {
+            self.set(x, y, p);
+        }
+    }
+
+    // Diffuses heat between neighbouring cells at the rate of the less conductive of each pair,
+    // reading from the grid and writing to a separate buffer so rows can be worked on in
+    // parallel without the result depending on order. Empty space also loses heat to the
+    // ambient temperature, which is how things cool down. Only awake chunks are conducted, and
+    // the chunks whose temperatures are still moving are returned as active.
+    fn conduct_heat(&mut self) -> Vec<bool> {
+        let (width, height) = (self.grid_width, self.grid_height);
+        let grid = &self.grid;
+        let materials = &*self.materials;
+        let chunks = &self.chunks;
+
+        self.heat.resize(grid.len(), 0.0);
+        self.heat
+            .par_chunks_mut(width)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, heat) in row.iter_mut().enumerate() {
+                    let p = grid[x + width * y];
+
+                    if !chunks.is_awake(chunks.index_of(x, y)) {
+                        *heat = p.temperature;
+                        continue;
+                    }
+
+                    let conductivity = materials[p.strain].conductivity;
+                    let mut t = p.temperature;
+
+                    for (vx, vy) in FOUR_ADJ_PARTICLES.iter() {
+                        let (ox, oy) = ((x as isize + vx) as usize, (y as isize + vy) as usize);
+
+                        if ox < width && oy < height {
+                            let other = grid[ox + width * oy];
+                            let rate = conductivity.min(materials[other.strain].conductivity)
+                                as f32
+                                / 100.0
+                                * HEAT_FLOW;
+                            t += (other.temperature - p.temperature) * rate;
+                        }
+                    }
+
+                    *heat = if p.strain == Strain::EMPTY {
+                        t + (AMBIENT_TEMPERATURE - t) * AIR_COOLING
+                    } else {
+                        t
+                    };
+                }
+            });
+
+        let mut active = vec![false; chunks.len()];
+        for (i, (p, &t)) in self.grid.iter_mut().zip(self.heat.iter()).enumerate() {
+            if (p.temperature - t).abs() > HEAT_SETTLED {
+                active[chunks.index_of(i % width, i / width)] = true;
+            }
+            p.temperature = t;
+        }
+
+        active
+    }
+
+    /// Advances the simulation by one tick, updating the grid a chunk at a time across rayon's
+    /// workers. The outcome depends only on the world's seed and the edits made to it, not on
+    /// how many threads there are.
+    pub fn step(&mut self) {
+        let mut active = self.conduct_heat();
+
+        let seed = self.rng.gen();
+        self.particles_updated = step_chunks(
+            &mut self.grid,
+            &self.materials,
+            &self.chunks,
+            &mut active,
+            self.update,
+            self.gravity,
+            seed,
+        );
+
+        self.chunks.settle(&active);
+        self.update = !self.update;
+    }
+}
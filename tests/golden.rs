@@ -0,0 +1,176 @@
+//! Golden scenes: small worlds stepped a fixed number of ticks from a fixed seed, then compared
+//! cell by cell with the grid they are expected to end up as.
+//!
+//! Each scene in `tests/scenes` is a few `ticks` and `seed` settings followed by its grid, one
+//! character per cell from `LEGEND`, and its expected result is the grid of the same name in
+//! `tests/golden`. After a change to the simulation that is meant to alter a scene, check the
+//! new results over and rewrite the goldens with
+//!
+//! ```text
+//! BLESS=1 cargo test --test golden
+//! ```
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use falling_sand_rs::{Materials, World};
+
+// the character each material is drawn with, by material id, which every built-in material
+// needs one of
+const LEGEND: &[(char, &str)] = &[
+    ('.', "empty"),
+    ('s', "sand"),
+    ('w', "water"),
+    ('#', "wood"),
+    ('f', "fire"),
+    ('g', "glass"),
+    ('G', "glass_molten"),
+    ('o', "oil_crude"),
+    ('a', "ash"),
+    ('A', "ash_burning"),
+    ('H', "wood_hot"),
+    ('~', "steam"),
+    ('i', "ice"),
+    ('*', "smoke"),
+    ('O', "oil_burning"),
+];
+
+fn path(dir: &str, name: &str) -> PathBuf {
+    [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        dir,
+        &format!("{}.txt", name),
+    ]
+    .iter()
+    .collect()
+}
+
+fn load_scene(name: &str) -> (World, u64) {
+    let text = fs::read_to_string(path("scenes", name)).unwrap();
+    let (mut ticks, mut seed) = (None, None);
+    let mut rows = Vec::new();
+
+    for line in text
+        .lines()
+        .filter(|l| !l.starts_with('#') && !l.is_empty())
+    {
+        match line.split_once(' ') {
+            Some(("ticks", n)) => ticks = Some(n.parse().unwrap()),
+            Some(("seed", n)) => seed = Some(n.parse().unwrap()),
+            _ => rows.push(line),
+        }
+    }
+
+    let mut world = World::with_seed(rows[0].len(), rows.len(), seed.expect("a seed"));
+    for (y, row) in rows.iter().enumerate() {
+        assert_eq!(
+            row.len(),
+            world.width(),
+            "{} row {} is the wrong width",
+            name,
+            y
+        );
+
+        for (x, c) in row.chars().enumerate() {
+            let (_, id) = LEGEND
+                .iter()
+                .find(|(l, _)| *l == c)
+                .expect("a known material");
+            let p = world.create_particle(world.materials().find(id).unwrap());
+            world.set(x, y, p);
+        }
+    }
+
+    (world, ticks.expect("a number of ticks"))
+}
+
+fn draw(world: &World) -> String {
+    let mut grid = String::new();
+
+    for y in 0..world.height() {
+        for x in 0..world.width() {
+            let id = &world.materials()[world.get(x, y).strain].id;
+            grid.push(LEGEND.iter().find(|(_, l)| l == id).unwrap().0);
+        }
+        grid.push('\n');
+    }
+
+    grid
+}
+
+// Steps the scene and checks it against its golden, or rewrites the golden when blessing
+fn check(name: &str) {
+    let (mut world, ticks) = load_scene(name);
+    for _ in 0..ticks {
+        world.step();
+    }
+    let found = draw(&world);
+
+    let golden = path("golden", name);
+    if env::var_os("BLESS").is_some() {
+        fs::write(&golden, &found).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&golden)
+        .unwrap_or_else(|e| panic!("{}: {}, run with BLESS=1 to create it", golden.display(), e));
+    let differing = expected
+        .chars()
+        .zip(found.chars())
+        .filter(|(e, f)| e != f)
+        .count();
+
+    assert!(
+        expected == found,
+        "{} differs from its golden in {} cells, got:\n{}",
+        name,
+        differing,
+        found
+    );
+}
+
+#[test]
+fn sand_piles_up() {
+    check("sand_pile");
+}
+
+#[test]
+fn water_levels_out() {
+    check("water_level");
+}
+
+#[test]
+fn oil_floats_on_water() {
+    check("oil_on_water");
+}
+
+#[test]
+fn wood_burns_to_ash() {
+    check("wood_burning");
+}
+
+#[test]
+fn sand_melts_into_glass() {
+    check("sand_melting");
+}
+
+#[test]
+fn every_material_has_its_own_legend_character() {
+    for material in Materials::builtin().iter() {
+        assert!(
+            LEGEND.iter().any(|(_, id)| *id == material.id),
+            "`{}` has no character in LEGEND",
+            material.id
+        );
+    }
+
+    for (i, (c, _)) in LEGEND.iter().enumerate() {
+        assert!(
+            LEGEND[i + 1..].iter().all(|(other, _)| other != c),
+            "'{}' is used twice in LEGEND",
+            c
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use falling_sand_rs::{History, Strain, World};
+
+fn grid(world: &World) -> Vec<(Strain, i16)> {
+    (0..world.height())
+        .flat_map(|y| (0..world.width()).map(move |x| (x, y)))
+        .map(|(x, y)| world.get(x, y))
+        .map(|p| (p.strain, p.lifetime))
+        .collect()
+}
+
+// fills the rectangle with fresh particles of `material`, recording a checkpoint first
+fn paint(
+    world: &mut World,
+    history: &mut History,
+    material: &str,
+    xs: std::ops::Range<usize>,
+    ys: std::ops::Range<usize>,
+) {
+    history.record(world);
+
+    let strain = world.materials().find(material).unwrap();
+    for y in ys {
+        for x in xs.clone() {
+            let p = world.create_particle(strain);
+            world.set(x, y, p);
+        }
+    }
+}
+
+#[test]
+fn undo_and_redo_step_through_every_edit() {
+    let mut world = World::with_seed(64, 48, 1);
+    let mut history = History::default();
+
+    let mut states = vec![grid(&world)];
+    for (i, material) in ["sand", "water", "wood", "empty"].iter().enumerate() {
+        paint(
+            &mut world,
+            &mut history,
+            material,
+            i * 8..i * 8 + 20,
+            10..30,
+        );
+        states.push(grid(&world));
+    }
+
+    for state in states.iter().rev().skip(1) {
+        assert!(history.undo(&mut world));
+        assert_eq!(&grid(&world), state);
+    }
+    assert!(!history.undo(&mut world));
+
+    for state in states.iter().skip(1) {
+        assert!(history.redo(&mut world));
+        assert_eq!(&grid(&world), state);
+    }
+    assert!(!history.redo(&mut world));
+}
+
+#[test]
+fn an_edit_after_undoing_drops_the_redos() {
+    let mut world = World::with_seed(32, 32, 2);
+    let mut history = History::default();
+
+    paint(&mut world, &mut history, "sand", 0..10, 0..10);
+    paint(&mut world, &mut history, "water", 10..20, 0..10);
+    assert!(history.undo(&mut world));
+    assert!(history.can_redo());
+
+    paint(&mut world, &mut history, "wood", 0..5, 20..25);
+    assert!(!history.can_redo());
+    assert!(!history.redo(&mut world));
+
+    let wood = grid(&world);
+    assert!(history.undo(&mut world));
+    assert!(history.redo(&mut world));
+    assert_eq!(grid(&world), wood);
+}
+
+#[test]
+fn the_oldest_checkpoints_are_forgotten_past_the_budget() {
+    let mut world = World::with_seed(128, 128, 3);
+    let checkpoint = 128 * 128 * std::mem::size_of::<falling_sand_rs::Particle>();
+    let mut history = History::new(checkpoint + 65536);
+
+    for i in 0..64 {
+        let material = if i % 2 == 0 { "sand" } else { "water" };
+        paint(&mut world, &mut history, material, i..i + 1, 0..128);
+        assert!(history.memory_used() <= checkpoint + 65536);
+    }
+    history.record(&world);
+
+    let mut undos = 0;
+    while history.undo(&mut world) {
+        undos += 1;
+    }
+    assert!(undos > 0 && undos < 64, "{} undos", undos);
+}
+
+#[test]
+fn large_fills_are_stored_compressed() {
+    let mut world = World::with_seed(512, 256, 4);
+    let mut history = History::default();
+
+    history.record(&world);
+    let checkpoint = history.memory_used();
+
+    paint(&mut world, &mut history, "wood", 0..512, 0..256);
+    paint(&mut world, &mut history, "empty", 0..512, 0..256);
+    history.record(&world);
+
+    // two whole-grid diffs, each of a single repeated particle
+    assert!(history.memory_used() - checkpoint < 1024);
+
+    assert!(history.undo(&mut world));
+    assert!(history.undo(&mut world));
+    assert!(history.memory_used() - checkpoint < 1024);
+    assert!(history.redo(&mut world));
+    assert!(grid(&world)
+        .iter()
+        .all(|&(s, _)| s == world.materials().find("wood").unwrap()));
+}
@@ -0,0 +1,28 @@
+use std::env;
+use std::fs;
+
+use falling_sand_rs::World;
+
+#[test]
+fn an_import_plays_out_the_same_for_the_same_seed() {
+    let path = env::temp_dir().join(format!("fs_import_{}.png", std::process::id()));
+
+    let mut scene = World::with_seed(32, 32, 1);
+    for (material, row) in [("fire", 10), ("steam", 20), ("wood", 31)] {
+        let strain = scene.materials().find(material).unwrap();
+        scene.set_row(strain, row);
+    }
+    scene.to_png(&path).unwrap();
+
+    let run = || {
+        let mut world = World::from_png(&path, 7).unwrap().world;
+        for _ in 0..40 {
+            world.step();
+        }
+        world.grid_hash()
+    };
+    let (first, second) = (run(), run());
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(first, second);
+}
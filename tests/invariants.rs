@@ -0,0 +1,187 @@
+//! Properties of a single step over random grids.
+
+use std::collections::HashMap;
+
+use falling_sand_rs::{
+    Materials, Movement, Particle, Strain, Transition, World, AMBIENT_TEMPERATURE,
+};
+use nalgebra::Vector2;
+use proptest::prelude::*;
+
+// Materials that stay what they are at room temperature, so a step only moves them around
+fn inert() -> Vec<Strain> {
+    let materials = Materials::builtin();
+    let above = |t: Option<Transition>| t.is_none_or(|t| t.at > AMBIENT_TEMPERATURE);
+
+    materials
+        .strains()
+        .filter(|&s| {
+            let m = &materials[s];
+            m.lifetime.is_none()
+                && m.burns_at.is_none()
+                && m.temperature == AMBIENT_TEMPERATURE
+                && m.reactions.is_empty()
+                && m.emits_above.is_empty()
+                && above(m.ignition)
+                && above(m.melting)
+                && above(m.boiling)
+                && m.freezing.is_none_or(|t| t.at < AMBIENT_TEMPERATURE)
+        })
+        .collect()
+}
+
+fn all() -> Vec<Strain> {
+    Materials::builtin().strains().collect()
+}
+
+#[derive(Clone, Debug)]
+struct Scene {
+    width: usize,
+    height: usize,
+    seed: u64,
+    upside_down: bool,
+    // material index and velocity of every cell, in row-major order
+    cells: Vec<(usize, f32, f32)>,
+}
+
+fn scene(materials: usize, max_size: usize) -> impl Strategy<Value = Scene> {
+    (1..=max_size, 1..=max_size, any::<u64>(), any::<bool>()).prop_flat_map(
+        move |(width, height, seed, upside_down)| {
+            // many at rest, so they have little distance to spare
+            let velocity = prop_oneof![Just((0.0, 0.0)), (-8.0f32..=8.0, -8.0f32..=8.0)];
+            let cell = (0..materials, velocity).prop_map(|(m, (vx, vy))| (m, vx, vy));
+            prop::collection::vec(cell, width * height).prop_map(move |cells| Scene {
+                width,
+                height,
+                seed,
+                upside_down,
+                cells,
+            })
+        },
+    )
+}
+
+// Builds the scene out of `materials`, tagging each particle with a lifetime that is unique to
+// it. Materials without a lifetime never touch it, so it follows the particle wherever it goes.
+fn build(scene: &Scene, materials: &[Strain]) -> World {
+    let mut world = World::with_seed(scene.width, scene.height, scene.seed);
+    if scene.upside_down {
+        world.set_gravity(-world.gravity());
+    }
+
+    for (i, &(m, vx, vy)) in scene.cells.iter().enumerate() {
+        let mut p = world.create_particle(materials[m]);
+        p.lifetime = -2 - i as i16;
+        p.velocity = Vector2::new(vx, vy);
+        world.set(i % scene.width, i / scene.width, p);
+    }
+
+    world
+}
+
+// where each tagged particle is
+fn positions(world: &World) -> HashMap<i16, (usize, usize)> {
+    let mut positions = HashMap::new();
+
+    for y in 0..world.height() {
+        for x in 0..world.width() {
+            let p = world.get(x, y);
+            if !world.is_particle_empty(x, y) {
+                positions.insert(p.lifetime, (x, y));
+            }
+        }
+    }
+
+    positions
+}
+
+// How far `p` may move in a tick: the furthest of its own moves, falling along its velocity,
+// tumbling or flowing, plus a cell for being pushed aside by something else, which happens at
+// most once a tick. Immovable solids stay put.
+fn allowed_distance(world: &World, p: &Particle) -> usize {
+    let material = &world.materials()[p.strain];
+
+    let own = match material.movement {
+        Movement::Static => return 0,
+        Movement::Gas | Movement::Flame => 1,
+        Movement::Powder | Movement::Liquid => {
+            let speed = p
+                .velocity
+                .x
+                .abs()
+                .max(p.velocity.y.abs() + world.gravity().abs())
+                .max(1.0);
+            (speed.ceil() as usize).max(material.dispersion as usize)
+        }
+    };
+
+    own + 1
+}
+
+proptest! {
+    #[test]
+    fn mass_is_conserved(scene in scene(inert().len(), 48)) {
+        let mut world = build(&scene, &inert());
+        let before = world.strain_counts();
+
+        world.step();
+
+        prop_assert_eq!(world.strain_counts(), before);
+    }
+
+    #[test]
+    fn particles_move_at_most_their_allowed_distance(scene in scene(inert().len(), 48)) {
+        let mut world = build(&scene, &inert());
+        let before = positions(&world);
+        let allowed: HashMap<_, _> = before
+            .iter()
+            .map(|(&tag, &(x, y))| (tag, allowed_distance(&world, &world.get(x, y))))
+            .collect();
+
+        world.step();
+
+        for (tag, (x, y)) in positions(&world) {
+            let (x0, y0) = before[&tag];
+            let distance = x.abs_diff(x0).max(y.abs_diff(y0));
+            prop_assert!(
+                distance <= allowed[&tag],
+                "a particle allowed {} cells moved from ({}, {}) to ({}, {})",
+                allowed[&tag],
+                x0,
+                y0,
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn immovable_materials_never_move(scene in scene(inert().len(), 48)) {
+        let mut world = build(&scene, &inert());
+        let solids: Vec<_> = positions(&world)
+            .into_iter()
+            .filter(|&(_, (x, y))| {
+                world.materials()[world.get(x, y).strain].movement == Movement::Static
+            })
+            .map(|(tag, (x, y))| (tag, (x, y), world.get(x, y).strain))
+            .collect();
+
+        world.step();
+
+        for (tag, (x, y), strain) in solids {
+            let after = world.get(x, y);
+            prop_assert_eq!((after.strain, after.lifetime), (strain, tag));
+        }
+    }
+
+    // Every material, at every size down to a single cell, either way up. Anything reading or
+    // writing outside the grid panics.
+    #[test]
+    fn stepping_stays_inside_the_grid(scene in scene(all().len(), 40)) {
+        let mut world = build(&scene, &all());
+
+        for _ in 0..4 {
+            world.step();
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use falling_sand_rs::{Strain, World};
+
+// rows of `strain` stacked in each column, counted from the floor
+fn depths(world: &World, strain: Strain) -> Vec<usize> {
+    (0..world.width())
+        .map(|x| {
+            (0..world.height())
+                .filter(|&y| world.get(x, y).strain == strain)
+                .count()
+        })
+        .collect()
+}
+
+fn fill(world: &mut World, strain: Strain, xs: std::ops::Range<usize>, ys: std::ops::Range<usize>) {
+    for y in ys {
+        for x in xs.clone() {
+            let p = world.create_particle(strain);
+            world.spawn_particle(x, y, p);
+        }
+    }
+}
+
+#[test]
+fn a_column_of_water_settles_flat() {
+    let mut world = World::with_seed(96, 64, 7);
+    let water = world.materials().find("water").unwrap();
+
+    // exactly four rows' worth, dropped against the left edge
+    fill(&mut world, water, 0..8, 16..64);
+
+    for _ in 0..1000 {
+        world.step();
+    }
+
+    assert_eq!(depths(&world, water), vec![4; 96]);
+}
+
+#[test]
+fn water_flows_away_from_the_left_edge() {
+    let mut world = World::with_seed(64, 32, 3);
+    let water = world.materials().find("water").unwrap();
+
+    fill(&mut world, water, 0..1, 22..32);
+
+    for _ in 0..300 {
+        world.step();
+    }
+
+    let depths = depths(&world, water);
+    assert_eq!(depths.iter().sum::<usize>(), 10);
+    assert!(depths.iter().all(|&d| d <= 1), "{:?}", depths);
+}
+
+#[test]
+fn oil_levels_out_on_water() {
+    let mut world = World::with_seed(64, 48, 11);
+    let water = world.materials().find("water").unwrap();
+    let oil = world.materials().find("oil_crude").unwrap();
+
+    fill(&mut world, water, 0..64, 40..48);
+    fill(&mut world, oil, 28..36, 0..16);
+
+    for _ in 0..2000 {
+        world.step();
+    }
+
+    assert_eq!(depths(&world, water), vec![8; 64]);
+    assert_eq!(depths(&world, oil), vec![2; 64]);
+    assert!((0..64).all(|x| world.get(x, 38).strain == oil && world.get(x, 39).strain == oil));
+}
+
+#[test]
+fn flowing_water_pushes_oil_back_one_cell() {
+    let mut world = World::with_seed(16, 2, 5);
+    let water = world.materials().find("water").unwrap();
+    let oil = world.materials().find("oil_crude").unwrap();
+    let wood = world.materials().find("wood").unwrap();
+
+    world.set_row(wood, 1);
+    fill(&mut world, water, 0..1, 0..1);
+    fill(&mut world, oil, 7..8, 0..1);
+
+    world.step();
+
+    // the water flows up to the oil and takes its place, and the oil, put back into the cell
+    // the water last crossed, can only have flowed on from there
+    let row: Vec<_> = (0..16).map(|x| world.get(x, 0).strain).collect();
+    assert_eq!(row[7], water);
+    let x = row.iter().position(|&s| s == oil).unwrap();
+    assert!((2..7).contains(&x), "oil ended up at {}", x);
+}
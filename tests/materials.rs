@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use falling_sand_rs::{MaterialError, Materials, Strain, World};
+
+const EMPTY: &str = "[[material]]\nid = \"empty\"\ncolour = [0.0, 0.0, 0.0]\n";
+
+// `empty`, then a material `thing` with the given fields
+fn parse(fields: &str) -> Result<Materials, MaterialError> {
+    Materials::parse(&format!(
+        "{}\n[[material]]\nid = \"thing\"\n{}\n",
+        EMPTY, fields
+    ))
+}
+
+const COLOUR: &str = "colour = [1.0, 0.5, 0.0]";
+
+#[test]
+fn the_builtin_materials_are_valid() {
+    let materials = Materials::builtin();
+    assert_eq!(materials.iter().next().unwrap().id, "empty");
+    assert!(materials.find("sand").is_some());
+}
+
+#[test]
+fn a_minimal_material_gets_defaults() {
+    let materials = parse(COLOUR).unwrap();
+    let thing = &materials[materials.find("thing").unwrap()];
+
+    assert_eq!(thing.name, "thing");
+    assert_eq!(thing.colour.a, 1.0);
+    assert_eq!(thing.flow_chance, 100);
+    assert_eq!(thing.lifetime, None);
+}
+
+#[test]
+fn file_errors_are_reported() {
+    assert!(matches!(
+        Materials::load("no/such/materials.toml"),
+        Err(MaterialError::Io(_))
+    ));
+    assert!(matches!(
+        Materials::parse("[[material]]\nid = "),
+        Err(MaterialError::Parse(_))
+    ));
+    assert!(matches!(
+        parse(&format!("{}\nsparkle = 3", COLOUR)),
+        Err(MaterialError::Parse(_))
+    ));
+}
+
+#[test]
+fn the_registry_must_be_well_formed() {
+    assert!(matches!(
+        Materials::parse("[[material]]\nid = \"sand\"\ncolour = [1.0, 1.0, 0.0]"),
+        Err(MaterialError::MissingEmpty)
+    ));
+
+    let many: String = (0..256)
+        .map(|i| format!("\n[[material]]\nid = \"m{}\"\n{}\n", i, COLOUR))
+        .collect();
+    assert!(matches!(
+        Materials::parse(&format!("{}{}", EMPTY, many)),
+        Err(MaterialError::TooMany(257))
+    ));
+
+    assert!(matches!(
+        Materials::parse(&format!("{}{}", EMPTY, EMPTY)),
+        Err(MaterialError::DuplicateId(id)) if id == "empty"
+    ));
+}
+
+#[test]
+fn fields_are_validated() {
+    let invalid = |fields: &str| parse(fields).err().map(|e| e.to_string());
+
+    assert!(matches!(parse(""), Err(MaterialError::MissingColour(id)) if id == "thing"));
+    for colour in ["[1.0, 0.0]", "[1.0, 0.0, 0.0, 1.0, 1.0]", "[2.0, 0.0, 0.0]"] {
+        assert!(
+            matches!(
+                parse(&format!("colour = {}", colour)),
+                Err(MaterialError::InvalidColour(_))
+            ),
+            "{}",
+            colour
+        );
+    }
+
+    for lifetime in ["[0, 10]", "[10, 10]", "[10, 5]"] {
+        assert!(matches!(
+            parse(&format!("{}\nlifetime = {}", COLOUR, lifetime)),
+            Err(MaterialError::InvalidLifetime(_))
+        ));
+    }
+
+    assert!(matches!(
+        parse(&format!("{}\nmovement = \"powder\"", COLOUR)),
+        Err(MaterialError::MissingDensity(_))
+    ));
+    assert!(matches!(
+        parse(&format!("{}\ndispersion = 9", COLOUR)),
+        Err(MaterialError::InvalidDispersion(_))
+    ));
+
+    assert_eq!(
+        invalid(&format!("{}\nmelting_point = 100.0", COLOUR)).unwrap(),
+        "material `thing` has a melting_point but nothing to turn into"
+    );
+    assert_eq!(
+        invalid(&format!("{}\ndies_into = \"nothing\"", COLOUR)).unwrap(),
+        "material `thing` dies_into refers to unknown material `nothing`"
+    );
+}
+
+#[test]
+fn every_percentage_is_out_of_100() {
+    let fields = [
+        ("conductivity", "conductivity = 101"),
+        ("splash", "splash = 101"),
+        ("flow_chance", "flow_chance = 101"),
+        ("ignite_chance", "ignite_chance = 101"),
+        (
+            "reaction chance",
+            "reactions = [{ with = \"empty\", chance = 101, into = \"empty\" }]",
+        ),
+        (
+            "emits_above chance",
+            "emits_above = { strain = \"empty\", chance = 101 }",
+        ),
+    ];
+
+    for (name, field) in fields {
+        match parse(&format!("{}\n{}", COLOUR, field)) {
+            Err(MaterialError::InvalidPercentage { material, field }) => {
+                assert_eq!((material.as_str(), field), ("thing", name))
+            }
+            other => panic!("{}: {:?}", name, other.map(|_| ())),
+        }
+
+        let at_most = field.replace("101", "100");
+        assert!(
+            parse(&format!("{}\n{}", COLOUR, at_most)).is_ok(),
+            "{}",
+            at_most
+        );
+    }
+}
+
+#[test]
+fn every_temperature_is_finite() {
+    let fields = [
+        ("temperature", "temperature = {}"),
+        ("burns_at", "burns_at = {}"),
+        (
+            "ignition_point",
+            "ignition_point = {}\nignites_into = \"empty\"",
+        ),
+        (
+            "melting_point",
+            "melting_point = {}\nmelts_into = \"empty\"",
+        ),
+        (
+            "boiling_point",
+            "boiling_point = {}\nboils_into = \"empty\"",
+        ),
+        (
+            "freezing_point",
+            "freezing_point = {}\nfreezes_into = \"empty\"",
+        ),
+    ];
+
+    for (name, field) in fields {
+        for value in ["nan", "inf", "-inf"] {
+            match parse(&format!("{}\n{}", COLOUR, field.replace("{}", value))) {
+                Err(MaterialError::InvalidTemperature { material, field }) => {
+                    assert_eq!((material.as_str(), field), ("thing", name))
+                }
+                other => panic!("{} = {}: {:?}", name, value, other.map(|_| ())),
+            }
+        }
+
+        let finite = field.replace("{}", "-40.5");
+        assert!(
+            parse(&format!("{}\n{}", COLOUR, finite)).is_ok(),
+            "{}",
+            finite
+        );
+    }
+}
+
+#[test]
+fn a_chance_of_0_never_happens() {
+    let materials = Arc::new(
+        parse(&format!(
+            "{}\nreactions = [{{ with = \"wet\", chance = 0, into = \"empty\" }}]\n\
+             emits_above = {{ strain = \"wet\", chance = 0 }}\n\
+             [[material]]\nid = \"wet\"\n{}",
+            COLOUR, COLOUR
+        ))
+        .unwrap(),
+    );
+    let thing = materials.find("thing").unwrap();
+    let wet = materials.find("wet").unwrap();
+
+    let mut world = World::with_materials(8, 8, 1, materials);
+    world.set_strain(3, 7, thing);
+    world.set_strain(4, 7, wet);
+    for _ in 0..2000 {
+        world.step();
+    }
+
+    assert_eq!(world.get(3, 7).strain, thing);
+    assert_eq!(world.get(3, 6).strain, Strain::EMPTY);
+}
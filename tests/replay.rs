@@ -0,0 +1,111 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use falling_sand_rs::replay::{read_replay, Command, VERSION};
+use falling_sand_rs::snapshot::write_snapshot;
+use falling_sand_rs::{
+    Brush, Drawing, History, Materials, Recorder, Replay, ReplayError, Tool, ToolInput, World,
+};
+
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("fs_{}_{}.rp", name, std::process::id()))
+}
+
+// Records a short session that paints sand, loads a snapshot of a water pool halfway through,
+// and keeps painting
+fn record(path: &PathBuf) -> World {
+    let mut world = World::with_seed(48, 32, 6);
+    let mut recorder = Recorder::create(path, &world, 1 << 20).unwrap();
+    let sand = world.materials().find("sand").unwrap();
+
+    let mut pool = World::with_seed(48, 32, 1);
+    let water = pool.materials().find("water").unwrap();
+    pool.set_row(water, 31);
+    let mut snapshot = Vec::new();
+    write_snapshot(&pool, &mut snapshot).unwrap();
+
+    let mut drawing = Drawing::default();
+    let mut history = History::default();
+
+    for frame in 0..120 {
+        if frame == 60 {
+            history.record(&world);
+            world.read_into(&mut &snapshot[..]).unwrap();
+            recorder.command(Command::Load(snapshot.clone()));
+        }
+
+        let input = ToolInput {
+            cursor: (frame % 48, 4),
+            down: frame % 3 != 0,
+            tool: Tool::Paint,
+            brush: Brush::default(),
+            strain: sand,
+        };
+        drawing.update(&mut world, &mut history, &input);
+        world.step();
+        recorder.frame(&input, 1, &world).unwrap();
+    }
+
+    recorder.finish(&world).unwrap();
+    world
+}
+
+#[test]
+fn a_session_with_a_load_plays_back_from_the_recording_alone() {
+    let path = temp_path("load");
+    let recorded = record(&path);
+
+    let replay = Replay::load(&path, &Materials::builtin());
+    fs::remove_file(&path).unwrap();
+    let replay = replay.unwrap();
+
+    let mut world = replay.world(Materials::builtin());
+    let mut playback = replay.playback();
+    while let Some(steps) = playback.edit(&mut world, |_, _| panic!("no console lines")) {
+        for _ in 0..steps {
+            world.step();
+        }
+        playback.verify(&world).unwrap();
+    }
+
+    assert_eq!(playback.ticks(), 120);
+    assert_eq!(world.grid_hash(), recorded.grid_hash());
+}
+
+#[test]
+fn a_recording_needs_the_materials_it_was_made_with() {
+    let path = temp_path("materials");
+    record(&path);
+
+    let source = fs::read_to_string(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/resources/materials.toml"
+    ))
+    .unwrap();
+    let other =
+        Materials::parse(&source.replacen("name = \"Sand\"", "name = \"Grit\"", 1)).unwrap();
+
+    let result = Replay::load(&path, &other);
+    fs::remove_file(&path).unwrap();
+
+    assert!(matches!(result, Err(ReplayError::WrongMaterials)));
+}
+
+#[test]
+fn oversized_headers_are_rejected() {
+    for (width, height) in [(0, 10), (10, 0), (65535, 65535), (u32::MAX, u32::MAX)] {
+        let mut bytes = b"FSRP".to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&6u64.to_le_bytes());
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&Materials::builtin().fingerprint().to_le_bytes());
+
+        assert!(matches!(
+            read_replay(&mut &bytes[..], &Materials::builtin()),
+            Err(ReplayError::InvalidDimensions { .. })
+        ));
+    }
+}
@@ -0,0 +1,84 @@
+#![cfg(feature = "scripting")]
+
+use falling_sand_rs::{Script, ScriptError, World};
+
+fn strain_at(world: &World, x: usize, y: usize) -> &str {
+    &world.materials()[world.get(x, y).strain].id
+}
+
+#[test]
+fn the_top_level_runs_when_loaded() {
+    let mut world = World::with_seed(16, 16, 1);
+
+    Script::compile(r#"for x in 0..width() { set(x, 15, "wood"); }"#, &mut world).unwrap();
+
+    assert!((0..16).all(|x| strain_at(&world, x, 15) == "wood"));
+    assert_eq!(strain_at(&world, 0, 14), "empty");
+}
+
+#[test]
+fn on_tick_is_called_once_a_tick() {
+    let mut world = World::with_seed(16, 16, 2);
+    let mut script = Script::compile(
+        r#"
+        fn on_tick(tick) {
+            spawn_particle(tick - 1, 0, "wood");
+        }
+        "#,
+        &mut world,
+    )
+    .unwrap();
+
+    for _ in 0..5 {
+        world.step();
+        script.tick(&mut world).unwrap();
+    }
+
+    let ticked: Vec<_> = (0..16)
+        .filter(|&x| strain_at(&world, x, 0) == "wood")
+        .collect();
+    assert_eq!(ticked, vec![0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn updates_skip_particles_replaced_earlier_in_the_tick() {
+    let mut world = World::with_seed(16, 16, 3);
+    let mut script = Script::compile(
+        r#"
+        set(0, 15, "wood");
+        set(1, 15, "wood");
+
+        // the first piece of wood turns the next into glass before its turn comes
+        on_update("wood", |x, y| {
+            set(x + 1, y, "glass");
+            set(x, y, "ash");
+        });
+        "#,
+        &mut world,
+    )
+    .unwrap();
+
+    script.tick(&mut world).unwrap();
+
+    assert_eq!(strain_at(&world, 0, 15), "ash");
+    assert_eq!(strain_at(&world, 1, 15), "glass");
+    assert_eq!(strain_at(&world, 2, 15), "empty");
+}
+
+#[test]
+fn runaway_loops_are_stopped() {
+    let mut world = World::with_seed(8, 8, 4);
+    assert!(matches!(
+        Script::compile("loop {}", &mut world),
+        Err(ScriptError::TooManyOperations)
+    ));
+
+    let mut script = Script::compile("fn on_tick(tick) { while true {} }", &mut world).unwrap();
+    assert!(matches!(
+        script.tick(&mut world),
+        Err(ScriptError::TooManyOperations)
+    ));
+
+    // the world is handed back either way
+    assert_eq!((world.width(), world.height()), (8, 8));
+}
@@ -0,0 +1,58 @@
+use falling_sand_rs::{Strain, World};
+
+// the heights of every particle of `strain`
+fn rows_of(world: &World, strain: Strain) -> Vec<usize> {
+    (0..world.height())
+        .flat_map(|y| (0..world.width()).map(move |x| (x, y)))
+        .filter(|&(x, y)| world.get(x, y).strain == strain)
+        .map(|(_, y)| y)
+        .collect()
+}
+
+#[test]
+fn a_settled_pile_falls_when_its_floor_is_erased() {
+    // either parity of tick, since sleeping particles miss every flip of the update flag
+    for settle in [20, 21] {
+        let mut world = World::with_seed(64, 64, 5);
+        let sand = world.materials().find("sand").unwrap();
+        let wood = world.materials().find("wood").unwrap();
+
+        world.set_row(wood, 40);
+        for x in 28..38 {
+            let p = world.create_particle(sand);
+            world.set(x, 36, p);
+        }
+
+        for _ in 0..settle {
+            world.step();
+        }
+        assert!(world.chunks().all(|c| !c.awake), "the pile has settled");
+
+        world.set_row(Strain::EMPTY, 40);
+        for _ in 0..100 {
+            world.step();
+        }
+
+        let rows = rows_of(&world, sand);
+        assert_eq!(rows.len(), 10);
+        assert!(rows.iter().all(|&y| y > 40), "{:?}", rows);
+    }
+}
+
+#[test]
+fn sand_painted_into_an_idle_world_falls() {
+    for idle in [10, 11] {
+        let mut world = World::with_seed(64, 64, 9);
+        let sand = world.materials().find("sand").unwrap();
+
+        for _ in 0..idle {
+            world.step();
+        }
+
+        let p = world.create_particle(sand);
+        world.set(10, 10, p);
+        world.step();
+
+        assert_eq!(rows_of(&world, sand), vec![11]);
+    }
+}
@@ -0,0 +1,167 @@
+use std::env;
+use std::fs;
+
+use falling_sand_rs::snapshot::{read_snapshot, write_snapshot, MAX_CELLS, VERSION};
+use falling_sand_rs::{Materials, SnapshotError, World};
+use nalgebra::Vector2;
+
+fn header(version: u16, width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = b"FSND".to_vec();
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(&width.to_le_bytes());
+    bytes.extend_from_slice(&height.to_le_bytes());
+    bytes
+}
+
+// a version 3 run of `len` cells of strain `id`
+fn run(bytes: &mut Vec<u8>, len: u32, id: u8) {
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.push(id);
+    bytes.extend_from_slice(&(-1i16).to_le_bytes());
+    for f in [20.0f32, 0.0, 0.0] {
+        bytes.extend_from_slice(&f.to_le_bytes());
+    }
+}
+
+fn read(bytes: &[u8]) -> Result<World, SnapshotError> {
+    read_snapshot(&mut &bytes[..], Materials::builtin())
+}
+
+#[test]
+fn a_world_round_trips() {
+    let mut world = World::with_seed(40, 30, 1);
+    let sand = world.materials().find("sand").unwrap();
+    let fire = world.materials().find("fire").unwrap();
+
+    for x in 0..40 {
+        let mut p = world.create_particle(sand);
+        p.temperature = x as f32 * 10.0;
+        p.velocity = Vector2::new(x as f32 / 4.0, -1.5);
+        world.set(x, 29, p);
+    }
+    let p = world.create_particle(fire);
+    world.set(20, 10, p);
+
+    let mut bytes = Vec::new();
+    write_snapshot(&world, &mut bytes).unwrap();
+    let loaded = read(&bytes).unwrap();
+
+    assert_eq!((loaded.width(), loaded.height()), (40, 30));
+    for y in 0..30 {
+        for x in 0..40 {
+            let (a, b) = (world.get(x, y), loaded.get(x, y));
+            assert_eq!(
+                (a.strain, a.lifetime, a.temperature, a.velocity),
+                (b.strain, b.lifetime, b.temperature, b.velocity)
+            );
+        }
+    }
+}
+
+#[test]
+fn older_versions_load_at_rest_and_room_temperature() {
+    let mut bytes = header(1, 2, 1);
+    bytes.extend_from_slice(&2u32.to_le_bytes());
+    bytes.push(1);
+    bytes.extend_from_slice(&(-1i16).to_le_bytes());
+
+    let world = read(&bytes).unwrap();
+    let sand = world.materials().find("sand").unwrap();
+    let p = world.get(1, 0);
+
+    assert_eq!(p.strain, sand);
+    assert_eq!(p.temperature, world.materials()[sand].base_temperature());
+    assert_eq!(p.velocity, Vector2::zeros());
+}
+
+#[test]
+fn errors_are_reported() {
+    let mut good = header(VERSION, 2, 2);
+    run(&mut good, 4, 1);
+    assert!(read(&good).is_ok());
+
+    let mut bad_magic = good.clone();
+    bad_magic[0] = b'X';
+    assert!(matches!(read(&bad_magic), Err(SnapshotError::BadMagic)));
+
+    for version in [0, VERSION + 1] {
+        let mut bytes = header(version, 2, 2);
+        run(&mut bytes, 4, 1);
+        assert!(matches!(
+            read(&bytes),
+            Err(SnapshotError::UnsupportedVersion(v)) if v == version
+        ));
+    }
+
+    for len in [3, good.len() - 1] {
+        assert!(matches!(read(&good[..len]), Err(SnapshotError::Truncated)));
+    }
+    let mut short = header(VERSION, 2, 2);
+    run(&mut short, 3, 1);
+    assert!(matches!(read(&short), Err(SnapshotError::Truncated)));
+
+    let mut unknown = header(VERSION, 2, 2);
+    run(&mut unknown, 4, 200);
+    assert!(matches!(
+        read(&unknown),
+        Err(SnapshotError::UnknownStrain(200))
+    ));
+
+    let mut overflow = header(VERSION, 2, 2);
+    run(&mut overflow, 3, 1);
+    run(&mut overflow, 2, 1);
+    assert!(matches!(read(&overflow), Err(SnapshotError::RunOverflow)));
+}
+
+#[test]
+fn oversized_headers_are_rejected_before_allocating() {
+    for (width, height) in [(0, 10), (10, 0), (65535, 65535)] {
+        assert!(matches!(
+            read(&header(VERSION, width, height)),
+            Err(SnapshotError::InvalidDimensions { .. })
+        ));
+    }
+
+    // within the limit, but with none of its cells
+    let side = (MAX_CELLS as f64).sqrt() as u32;
+    assert!(matches!(
+        read(&header(VERSION, side, side)),
+        Err(SnapshotError::Truncated)
+    ));
+}
+
+#[test]
+fn loading_into_a_world_of_another_size_fails() {
+    let path = env::temp_dir().join(format!("fs_snapshot_{}.bin", std::process::id()));
+    World::with_seed(8, 8, 1).save(&path).unwrap();
+
+    let result = World::with_seed(8, 9, 1).load_into(&path);
+    fs::remove_file(&path).unwrap();
+
+    assert!(matches!(
+        result,
+        Err(SnapshotError::DimensionMismatch {
+            expected: (8, 9),
+            found: (8, 8)
+        })
+    ));
+}
+
+#[test]
+fn a_loaded_world_keeps_simulating() {
+    let path = env::temp_dir().join(format!("fs_snapshot_load_{}.bin", std::process::id()));
+    let mut saved = World::with_seed(32, 32, 2);
+    let sand = saved.materials().find("sand").unwrap();
+    let p = saved.create_particle(sand);
+    saved.set(5, 5, p);
+    saved.save(&path).unwrap();
+
+    // a step first, so the world's update flag no longer matches a fresh particle's
+    let mut world = World::with_seed(32, 32, 3);
+    world.step();
+    world.load_into(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    world.step();
+    assert_eq!(world.get(5, 6).strain, sand);
+}